@@ -0,0 +1,183 @@
+/// This crate contains the batch mode, which processes many input files on a pool of worker
+/// threads. A file that cannot be processed is reported, but does not stop the others.
+pub mod batch {
+    use simp::ImageError;
+    use std::collections::HashMap;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::{fs, thread};
+
+    /// Returns the input files of a batch: the files in a directory, or the files that match a
+    /// glob pattern like `photos/*.ppm`, in alphabetical order.
+    ///
+    /// # Parameters:
+    ///  `pattern` - The directory or glob pattern
+    ///
+    /// # Returns:
+    ///  `Result<Vec<String>, ImageError>` - The input files, or the reason why they cannot be
+    ///  listed
+    pub fn inputs(pattern: &str) -> Result<Vec<String>, ImageError> {
+        let mut inputs = Vec::new();
+        if Path::new(pattern).is_dir() {
+            for entry in fs::read_dir(pattern)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    inputs.push(path.to_string_lossy().into_owned());
+                }
+            }
+        } else {
+            let paths = glob::glob(pattern)
+                .map_err(|err| ImageError::Format(format!("Invalid pattern {pattern}: {err}")))?;
+            for path in paths {
+                let path = path.map_err(|err| ImageError::Io(err.into()))?;
+                if path.is_file() {
+                    inputs.push(path.to_string_lossy().into_owned());
+                }
+            }
+        }
+        if inputs.is_empty() {
+            return Err(ImageError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No input files match {pattern}"),
+            )));
+        }
+        inputs.sort();
+        Ok(inputs)
+    }
+
+    /// Returns the output file of an input. A template with the placeholders `{name}` and
+    /// `{ext}`, e.g. `out/{name}.png`, gets the name of the input without its extension and the
+    /// extension. Any other template is a directory, where the output keeps the name of the
+    /// input. Missing directories are created.
+    ///
+    /// # Parameters:
+    ///  `template` - The output directory or naming template
+    ///  `input` - The input file
+    ///
+    /// # Returns:
+    ///  `Result<String, ImageError>` - The output file, or the reason why its directory cannot
+    ///  be created
+    pub fn output_name(template: &str, input: &str) -> Result<String, ImageError> {
+        let path = Path::new(input);
+        let output = if template.contains("{name}") || template.contains("{ext}") {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let ext = path.extension().unwrap_or_default().to_string_lossy();
+            template.replace("{name}", &name).replace("{ext}", &ext)
+        } else {
+            let name = path.file_name().unwrap_or_default();
+            Path::new(template)
+                .join(name)
+                .to_string_lossy()
+                .into_owned()
+        };
+        if let Some(parent) = Path::new(&output).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(output)
+    }
+
+    /// Returns the output files of a batch, one for each input, named like in `output_name`.
+    /// Every input needs its own output, which is none of the inputs, so that no file is
+    /// overwritten while it is still needed.
+    ///
+    /// # Parameters:
+    ///  `template` - The output directory or naming template
+    ///  `inputs` - The input files
+    ///
+    /// # Returns:
+    ///  `Result<Vec<String>, ImageError>` - The output files in the order of the inputs, or the
+    ///  reason why they cannot be used
+    pub fn outputs(template: &str, inputs: &[String]) -> Result<Vec<String>, ImageError> {
+        let mut files = HashMap::new();
+        for input in inputs {
+            files.insert(canonical(input)?, input);
+        }
+        let mut written = HashMap::new();
+        let mut outputs = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let output = output_name(template, input)?;
+            let path = canonical(&output)?;
+            if let Some(other) = files.get(&path) {
+                return Err(ImageError::Unsupported(format!(
+                    "The file {output} for {input} would overwrite the input {other}"
+                )));
+            }
+            if let Some(other) = written.insert(path, input) {
+                return Err(ImageError::Unsupported(format!(
+                    "The inputs {other} and {input} would both be written to {output}"
+                )));
+            }
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+
+    /// Returns the absolute location of a file without symbolic links. A file that does not
+    /// exist yet gets the location of its directory.
+    ///
+    /// # Parameters:
+    ///  `file` - The location of the file
+    fn canonical(file: &str) -> Result<PathBuf, ImageError> {
+        let path = Path::new(file);
+        match fs::canonicalize(path) {
+            Ok(path) => Ok(path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let parent = path
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                let name = path.file_name().unwrap_or_default();
+                Ok(fs::canonicalize(parent)?.join(name))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Returns the number of worker threads if none is given: one per available CPU core.
+    pub fn default_jobs() -> usize {
+        thread::available_parallelism().map_or(1, usize::from)
+    }
+
+    /// Processes every input on a pool of worker threads. Failures are printed to the standard
+    /// error as soon as they occur, and a summary follows after all inputs are processed.
+    ///
+    /// # Parameters:
+    ///  `inputs` - The input files
+    ///  `jobs` - The number of worker threads
+    ///  `process` - The processing of a single input
+    ///
+    /// # Returns:
+    ///  `usize` - The number of inputs that failed
+    pub fn run<F>(inputs: &[String], jobs: usize, process: F) -> usize
+    where
+        F: Fn(&str) -> Result<(), ImageError> + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let failed = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+                scope.spawn(|| {
+                    while let Some(input) = inputs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        if let Err(err) = process(input) {
+                            eprintln!("simp: {input}: {err}");
+                            failed.lock().unwrap().push(input);
+                        }
+                    }
+                });
+            }
+        });
+        let failed = failed.into_inner().unwrap();
+        eprintln!(
+            "Processed {} files: {} succeeded, {} failed",
+            inputs.len(),
+            inputs.len() - failed.len(),
+            failed.len()
+        );
+        for input in &failed {
+            eprintln!("  failed: {input}");
+        }
+        failed.len()
+    }
+}
@@ -0,0 +1,542 @@
+/// This crate contains the conversion between images and the Windows Bitmap (BMP) format.
+pub mod bmp {
+    use crate::error_utils::error::ImageError;
+    use crate::header_utils::header::TupleType;
+    use crate::image_utils::image::{Format, Image};
+    use crate::pixel_utils::pixel::Pixel;
+    use nalgebra::DMatrix;
+
+    /// The size of the file header, which starts with "BM".
+    const FILE_HEADER_SIZE: usize = 14;
+
+    /// The size of the BITMAPINFOHEADER, the most common info header.
+    const INFO_HEADER_SIZE: usize = 40;
+
+    /// Pixels are stored uncompressed.
+    const BI_RGB: u32 = 0;
+
+    /// Pixels are palette indices, compressed with run-length encoding.
+    const BI_RLE8: u32 = 1;
+
+    /// Pixels are stored uncompressed, and the color masks are given in the header.
+    const BI_BITFIELDS: u32 = 3;
+
+    /// The largest number of pixels of an RLE8 bitmap, i.e. 16384 by 16384 pixels. Unlike other
+    /// bitmaps, its size is not limited by the length of its data, since the rest of a row or of
+    /// the bitmap may be skipped.
+    const MAX_RLE8_PIXELS: usize = 1 << 28;
+
+    /// Reads a little-endian u16 at `offset`.
+    fn read_u16(data: &[u8], offset: usize) -> Result<u16, ImageError> {
+        match data.get(offset..offset + 2) {
+            Some(bytes) => Ok(u16::from_le_bytes([bytes[0], bytes[1]])),
+            None => Err(ImageError::Format(
+                "Unexpected end of the bitmap header".to_string(),
+            )),
+        }
+    }
+
+    /// Reads a little-endian u32 at `offset`.
+    fn read_u32(data: &[u8], offset: usize) -> Result<u32, ImageError> {
+        match data.get(offset..offset + 4) {
+            Some(bytes) => Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            None => Err(ImageError::Format(
+                "Unexpected end of the bitmap header".to_string(),
+            )),
+        }
+    }
+
+    /// Returns the size of a row of `width` pixels with `bits` bits each, padded to four bytes.
+    fn row_size(width: usize, bits: usize) -> Result<usize, ImageError> {
+        Ok(Image::data_size(&[width, bits])?.div_ceil(32) * 4)
+    }
+
+    /// Extracts the sample selected by `mask` from `value` and scales it to 8 bits.
+    fn masked_sample(value: u32, mask: u32) -> u16 {
+        if mask == 0 {
+            return 0;
+        }
+        let sample = u64::from((value & mask) >> mask.trailing_zeros());
+        let max = u64::from(mask >> mask.trailing_zeros());
+        u16::try_from((sample * 255 + max / 2) / max).unwrap()
+    }
+
+    /// Decodes a BMP file. Supported are uncompressed images with 24 or 32 bits per pixel,
+    /// including color masks, and images with 8-bit palette indices, either uncompressed or
+    /// compressed with RLE8. Rows may be stored bottom-up or top-down. The image gets the magic
+    /// number P6, or P7 if it has an alpha mask.
+    ///
+    /// # Parameters:
+    ///  `contents` - The raw bytes of the file
+    ///
+    /// # Returns:
+    ///  `Result<Image, ImageError>` - The image or the reason why it could not be decoded
+    pub fn decode(contents: &[u8]) -> Result<Image, ImageError> {
+        if !contents.starts_with(b"BM") {
+            return Err(ImageError::Format("Missing BM signature".to_string()));
+        }
+        let data_offset = read_u32(contents, 10)? as usize;
+        let header_size = read_u32(contents, FILE_HEADER_SIZE)? as usize;
+        if header_size < INFO_HEADER_SIZE {
+            return Err(ImageError::Unsupported(
+                "Unsupported bitmap header".to_string(),
+            ));
+        }
+        let info = FILE_HEADER_SIZE;
+        let width = read_u32(contents, info + 4)? as i32;
+        let height = read_u32(contents, info + 8)? as i32;
+        let bits = read_u16(contents, info + 14)?;
+        let compression = read_u32(contents, info + 16)?;
+        let colors_used = read_u32(contents, info + 32)? as usize;
+        if width <= 0 || height == 0 {
+            return Err(ImageError::Dimensions(
+                "Invalid bitmap dimensions".to_string(),
+            ));
+        }
+        let top_down = height < 0;
+        let width = width.unsigned_abs() as usize;
+        let height = height.unsigned_abs() as usize;
+        let data = contents.get(data_offset..).ok_or_else(|| {
+            ImageError::Format("Pixel data beyond the end of the file".to_string())
+        })?;
+        let mut rows = match (bits, compression) {
+            (24, BI_RGB) => decode_true_color(data, width, height, 24, [0xff0000, 0xff00, 0xff, 0]),
+            (32, BI_RGB) => decode_true_color(data, width, height, 32, [0xff0000, 0xff00, 0xff, 0]),
+            (32, BI_BITFIELDS) => {
+                // The masks follow the info header, or are part of the larger V4 and V5 headers.
+                let masks = info + INFO_HEADER_SIZE;
+                let alpha = if header_size > INFO_HEADER_SIZE + 12 {
+                    read_u32(contents, masks + 12)?
+                } else {
+                    0
+                };
+                let masks = [
+                    read_u32(contents, masks)?,
+                    read_u32(contents, masks + 4)?,
+                    read_u32(contents, masks + 8)?,
+                    alpha,
+                ];
+                decode_true_color(data, width, height, 32, masks)
+            }
+            (8, BI_RGB | BI_RLE8) => {
+                let count = if colors_used == 0 { 256 } else { colors_used };
+                let start = FILE_HEADER_SIZE + header_size;
+                let palette = Image::data_size(&[4, count])
+                    .ok()
+                    .and_then(|size| contents.get(start..start.checked_add(size)?))
+                    .ok_or_else(|| {
+                        ImageError::Format("Unexpected end of the palette".to_string())
+                    })?;
+                let palette: Vec<Pixel> = palette
+                    .chunks(4)
+                    .map(|bgr| {
+                        Pixel {
+                            red: bgr[2].into(),
+                            green: bgr[1].into(),
+                            blue: bgr[0].into(),
+                            alpha: 255,
+                        }
+                        .normalize(255)
+                    })
+                    .collect();
+                let indices = if compression == BI_RLE8 {
+                    decode_rle8(data, width, height)?
+                } else {
+                    decode_indices(data, width, height)?
+                };
+                indices
+                    .into_iter()
+                    .map(|index| palette.get(usize::from(index)).copied())
+                    .collect::<Option<Vec<Pixel>>>()
+                    .ok_or_else(|| ImageError::Format("Palette index out of range".to_string()))
+            }
+            _ => Err(ImageError::Unsupported(
+                "Unsupported bit depth or compression".to_string(),
+            )),
+        }?;
+        if !top_down {
+            // Bottom-up bitmaps store the last row first.
+            rows = rows
+                .chunks(width)
+                .rev()
+                .flat_map(<[Pixel]>::to_vec)
+                .collect();
+        }
+        let has_alpha = bits == 32
+            && compression == BI_BITFIELDS
+            && rows.iter().any(|pixel| pixel.alpha < u16::MAX);
+        Ok(Image {
+            format: Format::Bmp,
+            magic_number: if has_alpha { "P7" } else { "P6" }.to_string(),
+            scale: 255,
+            comments: Vec::new(),
+            tuple_type: has_alpha.then(|| TupleType {
+                name: "RGB_ALPHA".to_string(),
+                depth: 4,
+            }),
+            pixels: DMatrix::from_row_slice(height, width, &rows),
+        })
+    }
+
+    /// Decodes uncompressed pixels with 24 or 32 bits, in the order they are stored.
+    ///
+    /// # Parameters:
+    ///  `data` - The pixel data
+    ///  `width` - The width of the image
+    ///  `height` - The height of the image
+    ///  `bits` - The bits per pixel, 24 or 32
+    ///  `masks` - The masks of the red, green, blue and alpha samples, where an alpha mask of zero
+    ///  stands for an opaque image
+    fn decode_true_color(
+        data: &[u8],
+        width: usize,
+        height: usize,
+        bits: usize,
+        masks: [u32; 4],
+    ) -> Result<Vec<Pixel>, ImageError> {
+        let row_size = row_size(width, bits)?;
+        let bytes = bits / 8;
+        if data.len() < Image::data_size(&[row_size, height])? {
+            return Err(ImageError::Dimensions(
+                "Insufficient data for the specified dimensions".to_string(),
+            ));
+        }
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in data.chunks(row_size).take(height) {
+            for pixel in row[..width * bytes].chunks(bytes) {
+                let mut value = [0; 4];
+                value[..bytes].copy_from_slice(pixel);
+                let value = u32::from_le_bytes(value);
+                let [red, green, blue, alpha] = masks;
+                let alpha = if alpha == 0 {
+                    255
+                } else {
+                    masked_sample(value, alpha)
+                };
+                pixels.push(
+                    Pixel {
+                        red: masked_sample(value, red),
+                        green: masked_sample(value, green),
+                        blue: masked_sample(value, blue),
+                        alpha,
+                    }
+                    .normalize(255),
+                );
+            }
+        }
+        Ok(pixels)
+    }
+
+    /// Decodes uncompressed 8-bit palette indices, in the order they are stored.
+    ///
+    /// # Parameters:
+    ///  `data` - The pixel data
+    ///  `width` - The width of the image
+    ///  `height` - The height of the image
+    fn decode_indices(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, ImageError> {
+        let row_size = row_size(width, 8)?;
+        if data.len() < Image::data_size(&[row_size, height])? {
+            return Err(ImageError::Dimensions(
+                "Insufficient data for the specified dimensions".to_string(),
+            ));
+        }
+        Ok(data
+            .chunks(row_size)
+            .take(height)
+            .flat_map(|row| row[..width].to_vec())
+            .collect())
+    }
+
+    /// Decodes 8-bit palette indices compressed with RLE8, in the order they are stored. Pairs
+    /// of a count and an index repeat the index. A count of zero escapes: 0 ends the row, 1 ends
+    /// the bitmap, 2 moves the position by the next two bytes, and any other value is followed by
+    /// that many literal indices, padded to an even number of bytes. Skipped pixels get index 0.
+    /// Bitmaps with more than `MAX_RLE8_PIXELS` pixels are rejected.
+    ///
+    /// # Parameters:
+    ///  `data` - The compressed pixel data
+    ///  `width` - The width of the image
+    ///  `height` - The height of the image
+    fn decode_rle8(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, ImageError> {
+        let size = Image::data_size(&[width, height])?;
+        if size > MAX_RLE8_PIXELS {
+            return Err(ImageError::Dimensions("RLE8 bitmap too large".to_string()));
+        }
+        let mut indices = vec![0; size];
+        let (mut x, mut y) = (0, 0);
+        let mut position = 0;
+        let mut next = || -> Result<usize, ImageError> {
+            let byte = data
+                .get(position)
+                .ok_or_else(|| ImageError::Format("Unexpected end of the RLE8 data".to_string()))?;
+            position += 1;
+            Ok(usize::from(*byte))
+        };
+        let mut put = |x: usize, y: usize, index: usize| {
+            if x < width && y < height {
+                indices[y * width + x] = u8::try_from(index).unwrap();
+            }
+        };
+        while y < height {
+            let count = next()?;
+            let value = next()?;
+            match (count, value) {
+                (0, 0) => {
+                    x = 0;
+                    y += 1;
+                }
+                (0, 1) => break,
+                (0, 2) => {
+                    x += next()?;
+                    y += next()?;
+                }
+                (0, literal) => {
+                    for _ in 0..literal {
+                        put(x, y, next()?);
+                        x += 1;
+                    }
+                    if literal % 2 == 1 {
+                        next()?;
+                    }
+                }
+                (count, index) => {
+                    for _ in 0..count {
+                        put(x, y, index);
+                        x += 1;
+                    }
+                }
+            }
+        }
+        Ok(indices)
+    }
+
+    /// Encodes an image as uncompressed BMP file with 24 bits per pixel, stored bottom-up. The
+    /// alpha channel is dropped.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to encode
+    ///
+    /// # Returns:
+    ///  `Result<Vec<u8>, ImageError>` - The contents of the file, or the reason why it could
+    ///  not be encoded
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn encode(image: &Image) -> Result<Vec<u8>, ImageError> {
+        let (height, width) = image.pixels.shape();
+        let row_size = row_size(width, 24)?;
+        let data_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+        let file_size = data_offset + row_size * height;
+        let to_u32 = |value: usize| {
+            u32::try_from(value)
+                .map_err(|_| ImageError::Dimensions("Image too large for BMP".to_string()))
+        };
+        let mut contents = Vec::with_capacity(file_size);
+        // File header
+        contents.extend_from_slice(b"BM");
+        contents.extend_from_slice(&to_u32(file_size)?.to_le_bytes());
+        contents.extend_from_slice(&[0; 4]);
+        contents.extend_from_slice(&to_u32(data_offset)?.to_le_bytes());
+        // Info header
+        contents.extend_from_slice(&to_u32(INFO_HEADER_SIZE)?.to_le_bytes());
+        contents.extend_from_slice(&to_u32(width)?.to_le_bytes());
+        contents.extend_from_slice(&to_u32(height)?.to_le_bytes());
+        contents.extend_from_slice(&1u16.to_le_bytes());
+        contents.extend_from_slice(&24u16.to_le_bytes());
+        contents.extend_from_slice(&BI_RGB.to_le_bytes());
+        contents.extend_from_slice(&to_u32(row_size * height)?.to_le_bytes());
+        // 2835 pixels per meter are 72 dpi
+        contents.extend_from_slice(&2835u32.to_le_bytes());
+        contents.extend_from_slice(&2835u32.to_le_bytes());
+        contents.extend_from_slice(&[0; 8]);
+        // Pixels
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let pixel = image.pixels[(y, x)].denormalize(255);
+                contents.extend_from_slice(&[pixel.blue as u8, pixel.green as u8, pixel.red as u8]);
+            }
+            contents.resize(contents.len() + row_size - 3 * width, 0);
+        }
+        Ok(contents)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Returns the headers of a bitmap with the given palette, after which the pixel data
+        /// follows.
+        fn headers(
+            width: i32,
+            height: i32,
+            bits: u16,
+            compression: u32,
+            palette: &[u8],
+        ) -> Vec<u8> {
+            let data_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE + palette.len();
+            let mut contents = b"BM".to_vec();
+            contents.extend_from_slice(&[0; 8]);
+            contents.extend_from_slice(&u32::try_from(data_offset).unwrap().to_le_bytes());
+            contents.extend_from_slice(&u32::try_from(INFO_HEADER_SIZE).unwrap().to_le_bytes());
+            contents.extend_from_slice(&width.to_le_bytes());
+            contents.extend_from_slice(&height.to_le_bytes());
+            contents.extend_from_slice(&1u16.to_le_bytes());
+            contents.extend_from_slice(&bits.to_le_bytes());
+            contents.extend_from_slice(&compression.to_le_bytes());
+            contents.extend_from_slice(&[0; 12]);
+            let colors = u32::try_from(palette.len() / 4).unwrap();
+            contents.extend_from_slice(&colors.to_le_bytes());
+            contents.extend_from_slice(&[0; 4]);
+            contents.extend_from_slice(palette);
+            contents
+        }
+
+        /// Returns the opaque pixel with the given 8-bit samples.
+        fn rgb(red: u16, green: u16, blue: u16) -> Pixel {
+            Pixel {
+                red,
+                green,
+                blue,
+                alpha: 255,
+            }
+            .normalize(255)
+        }
+
+        /// The palette of the indexed bitmaps: black, red and white, stored as blue, green, red
+        /// and a reserved byte.
+        const PALETTE: [u8; 12] = [0, 0, 0, 0, 0, 0, 255, 0, 255, 255, 255, 0];
+
+        #[test]
+        fn true_color_round_trip() {
+            let pixels = DMatrix::from_row_slice(
+                2,
+                3,
+                &[
+                    rgb(0, 0, 0),
+                    rgb(255, 0, 0),
+                    rgb(0, 255, 0),
+                    rgb(0, 0, 255),
+                    rgb(1, 2, 3),
+                    rgb(255, 255, 255),
+                ],
+            );
+            let image = Image {
+                format: Format::Bmp,
+                magic_number: "P6".to_string(),
+                scale: 255,
+                comments: Vec::new(),
+                tuple_type: None,
+                pixels,
+            };
+            let contents = encode(&image).unwrap();
+            // Rows of 3 pixels are padded from 9 to 12 bytes.
+            assert_eq!(contents.len(), FILE_HEADER_SIZE + INFO_HEADER_SIZE + 2 * 12);
+            let decoded = decode(&contents).unwrap();
+            assert_eq!(decoded.magic_number, "P6");
+            assert_eq!(decoded.pixels, image.pixels);
+        }
+
+        #[test]
+        fn reads_bottom_up_and_top_down_rows() {
+            let rows = [[0, 0, 255, 0], [255, 0, 0, 0]];
+            let mut bottom_up = headers(1, 2, 24, BI_RGB, &[]);
+            bottom_up.extend(rows.concat());
+            let mut top_down = headers(1, -2, 24, BI_RGB, &[]);
+            top_down.extend(rows.iter().rev().flatten());
+            for contents in [bottom_up, top_down] {
+                let image = decode(&contents).unwrap();
+                assert_eq!(image.pixels.shape(), (2, 1));
+                assert_eq!(image.pixels[(0, 0)], rgb(0, 0, 255));
+                assert_eq!(image.pixels[(1, 0)], rgb(255, 0, 0));
+            }
+        }
+
+        #[test]
+        fn reads_alpha_masks() {
+            let mut contents = headers(2, 1, 32, BI_BITFIELDS, &[]);
+            // The masks of red, green, blue and alpha follow the info header of a V4 header.
+            contents[FILE_HEADER_SIZE..FILE_HEADER_SIZE + 4].copy_from_slice(&108u32.to_le_bytes());
+            for mask in [0xff0000u32, 0xff00, 0xff, 0xff00_0000] {
+                contents.extend_from_slice(&mask.to_le_bytes());
+            }
+            contents.resize(FILE_HEADER_SIZE + 108, 0);
+            let data_offset = u32::try_from(contents.len()).unwrap();
+            contents[10..14].copy_from_slice(&data_offset.to_le_bytes());
+            contents.extend_from_slice(&[3, 2, 1, 0x80, 0, 0, 0, 0xff]);
+            let image = decode(&contents).unwrap();
+            assert_eq!(image.magic_number, "P7");
+            assert_eq!(
+                image.pixels[(0, 0)],
+                Pixel {
+                    red: 1,
+                    green: 2,
+                    blue: 3,
+                    alpha: 0x80
+                }
+                .normalize(255)
+            );
+            assert_eq!(image.pixels[(0, 1)], rgb(0, 0, 0));
+        }
+
+        #[test]
+        fn reads_palette_indices() {
+            let mut contents = headers(3, 1, 8, BI_RGB, &PALETTE);
+            contents.extend_from_slice(&[2, 1, 0, 0]);
+            let image = decode(&contents).unwrap();
+            let expected = [rgb(255, 255, 255), rgb(255, 0, 0), rgb(0, 0, 0)];
+            assert_eq!(image.pixels.as_slice(), expected);
+        }
+
+        #[test]
+        fn reads_rle8() {
+            let mut contents = headers(4, 3, 8, BI_RLE8, &PALETTE);
+            contents.extend_from_slice(&[
+                // Bottom row: one red pixel, three literal pixels with padding, end of the row.
+                1, 1, 0, 3, 1, 2, 0, 0, 0, 0, //
+                // Middle row: one white pixel, then one pixel right and one row up.
+                1, 2, 0, 2, 1, 1, //
+                // Top row: one red pixel, then the end of the bitmap.
+                1, 1, 0, 1,
+            ]);
+            let image = decode(&contents).unwrap();
+            let (black, red, white) = (rgb(0, 0, 0), rgb(255, 0, 0), rgb(255, 255, 255));
+            let rows = [
+                [black, black, red, black],
+                [white, black, black, black],
+                [red, red, white, black],
+            ];
+            for (y, row) in rows.iter().enumerate() {
+                for (x, pixel) in row.iter().enumerate() {
+                    assert_eq!(image.pixels[(y, x)], *pixel, "pixel {x}, {y}");
+                }
+            }
+        }
+
+        #[test]
+        fn rejects_invalid_and_oversized_bitmaps() {
+            let mut truncated = headers(2, 2, 24, BI_RGB, &[]);
+            truncated.extend_from_slice(&[0; 12]);
+            let mut out_of_palette = headers(1, 1, 8, BI_RGB, &PALETTE);
+            out_of_palette.extend_from_slice(&[3, 0, 0, 0]);
+            let mut unterminated = headers(2, 2, 8, BI_RLE8, &PALETTE);
+            unterminated.extend_from_slice(&[2, 1]);
+            let mut huge_rle8 = headers(i32::MAX, i32::MAX, 8, BI_RLE8, &PALETTE);
+            huge_rle8.extend_from_slice(&[0, 1]);
+            let mut huge = headers(i32::MAX, i32::MIN, 32, BI_RGB, &[]);
+            huge.extend_from_slice(&[0; 8]);
+            let mut empty = headers(0, 1, 24, BI_RGB, &[]);
+            empty.extend_from_slice(&[0; 4]);
+            for contents in [
+                b"BM".to_vec(),
+                b"MB".to_vec(),
+                truncated,
+                out_of_palette,
+                unterminated,
+                huge_rle8,
+                huge,
+                empty,
+                headers(1, 1, 16, BI_RGB, &[]),
+            ] {
+                assert!(decode(&contents).is_err());
+            }
+        }
+    }
+}
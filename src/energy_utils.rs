@@ -1,24 +1,23 @@
 /// Seam Carving uses color differences of neighboring pixels as dispensability score. This
-/// difference is called energy. This crate contains methods to calculate the energy of an image
-/// and to find the optimal path according to this dispensability score.
-
+/// difference is called energy. This crate contains methods to calculate the energy of an image,
+/// to find the optimal path according to this dispensability score and to carve it.
 pub mod energy {
-    use crate::image_utils::image::Image;
-    use crate::pixel_utils::pixel::Pixel;
+    use crate::pixel_utils::pixel::ColorDiff;
     use nalgebra::DMatrix;
     use std::cmp::min;
 
     /// Pixels have local energy which is the sum of the color differences of the current pixel and
     /// its left and upper neighbor (if present). The total energy of a pixel is calculated by
-    /// adding the minimum of the total energy of the three pixels above the current pixels.
+    /// adding the minimum of the total energy of the three pixels above the current pixels. The
+    /// sums saturate, as color differences of floating point pixels may be arbitrarily large.
     ///
     /// # Parameters
-    ///  `image` - the pixel matrix
+    ///  `pixels` - the pixel matrix
     ///  `energy` - the allocated energy matrix
     ///  `border` - the width up to which column in the image the energy should be calculated
-    pub fn calculate_vertical_energy_matrix(
-        image: &Image,
-        energy: &mut DMatrix<u32>,
+    pub fn calculate_vertical_energy_matrix<P: ColorDiff>(
+        pixels: &DMatrix<P>,
+        energy: &mut DMatrix<u64>,
         border: usize,
     ) {
         // Calculation of local energy
@@ -28,40 +27,45 @@ pub mod energy {
         for j in 1..border {
             let current = (0, j);
             let left = (0, j - 1);
-            energy[current] = Pixel::color_diff(image.pixels[current], image.pixels[left]);
+            energy[current] = P::color_diff(pixels[current], pixels[left]);
         }
         // Edge Case: Left Border
-        for i in 1..image.pixels.nrows() {
+        for i in 1..pixels.nrows() {
             let current = (i, 0);
             let above = (i - 1, 0);
-            energy[current] = Pixel::color_diff(image.pixels[current], image.pixels[above]);
+            energy[current] = P::color_diff(pixels[current], pixels[above]);
         }
         // No Edge Cases
-        for i in 1..image.pixels.nrows() {
+        for i in 1..pixels.nrows() {
             for j in 1..border {
                 let current = (i, j);
                 let left = (i, j - 1);
                 let above = (i - 1, j);
-                energy[current] = Pixel::color_diff(image.pixels[current], image.pixels[left])
-                    + Pixel::color_diff(image.pixels[current], image.pixels[above]);
+                energy[current] = P::color_diff(pixels[current], pixels[left])
+                    .saturating_add(P::color_diff(pixels[current], pixels[above]));
             }
         }
         // Calculation of total energy
-        for i in 1..image.pixels.nrows() {
+        for i in 1..pixels.nrows() {
             for j in 0..border {
                 let current = (i, j);
-                let left = (i - 1, j - 1);
                 let above = (i - 1, j);
                 let right = (i - 1, j + 1);
                 if j == 0 {
                     // Edge Case: Left Border
-                    energy[current] += min(energy[above], energy[right]);
-                } else if j == border - 1 {
+                    energy[current] =
+                        energy[current].saturating_add(min(energy[above], energy[right]));
+                    continue;
+                }
+                let left = (i - 1, j - 1);
+                if j == border - 1 {
                     // Edge Case: Right Border
-                    energy[current] += min(energy[above], energy[left]);
+                    energy[current] =
+                        energy[current].saturating_add(min(energy[above], energy[left]));
                 } else {
                     // No Edge Cases
-                    energy[current] += min(min(energy[above], energy[left]), energy[right]);
+                    energy[current] = energy[current]
+                        .saturating_add(min(min(energy[above], energy[left]), energy[right]));
                 }
             }
         }
@@ -69,15 +73,16 @@ pub mod energy {
 
     /// Pixels have local energy which is the sum of the color differences of the current pixel and
     /// its left and lower neighbor (if present). The total energy of a pixel is calculated by
-    /// adding the minimum of the total energy of the three pixels left to the current pixel.
+    /// adding the minimum of the total energy of the three pixels left to the current pixel. The
+    /// sums saturate, like in `calculate_vertical_energy_matrix`.
     ///
     /// # Parameters
-    ///  `image` - the pixel matrix
+    ///  `pixels` - the pixel matrix
     ///  `energy` - the allocated energy matrix
     ///  `border` - the height up to which row in the image the energy should be calculated
-    pub fn calculate_horizontal_energy_matrix(
-        image: &Image,
-        energy: &mut DMatrix<u32>,
+    pub fn calculate_horizontal_energy_matrix<P: ColorDiff>(
+        pixels: &DMatrix<P>,
+        energy: &mut DMatrix<u64>,
         border: usize,
     ) {
         // Calculation of local energy
@@ -87,47 +92,52 @@ pub mod energy {
         for j in 1..border {
             let current = (j, 0);
             let left = (j - 1, 0);
-            energy[current] = Pixel::color_diff(image.pixels[current], image.pixels[left]);
+            energy[current] = P::color_diff(pixels[current], pixels[left]);
         }
         // Edge Case: First Row
-        for i in 1..image.pixels.ncols() {
+        for i in 1..pixels.ncols() {
             let current = (0, i);
             let lower = (0, i - 1);
-            energy[current] = Pixel::color_diff(image.pixels[current], image.pixels[lower]);
+            energy[current] = P::color_diff(pixels[current], pixels[lower]);
         }
         // No Edge Cases
-        for i in 1..image.pixels.ncols() {
+        for i in 1..pixels.ncols() {
             for j in 1..border {
                 let current = (j, i);
                 let left = (j - 1, i);
                 let lower = (j, i - 1);
-                energy[current] = Pixel::color_diff(image.pixels[current], image.pixels[left])
-                    + Pixel::color_diff(image.pixels[current], image.pixels[lower]);
+                energy[current] = P::color_diff(pixels[current], pixels[left])
+                    .saturating_add(P::color_diff(pixels[current], pixels[lower]));
             }
         }
         // Calculation of total energy
-        for i in 1..image.pixels.ncols() {
+        for i in 1..pixels.ncols() {
             for j in 0..border {
                 let current = (j, i);
-                let left = (j - 1, i - 1);
                 let lower = (j, i - 1);
                 let right = (j + 1, i - 1);
                 if j == 0 {
                     // Edge Case: Left Border
-                    energy[current] += min(energy[lower], energy[right]);
-                } else if j == border - 1 {
+                    energy[current] =
+                        energy[current].saturating_add(min(energy[lower], energy[right]));
+                    continue;
+                }
+                let left = (j - 1, i - 1);
+                if j == border - 1 {
                     // Edge Case: Right Border
-                    energy[current] += min(energy[lower], energy[left]);
+                    energy[current] =
+                        energy[current].saturating_add(min(energy[lower], energy[left]));
                 } else {
                     // No Edge Cases
-                    energy[current] += min(min(energy[lower], energy[left]), energy[right]);
+                    energy[current] = energy[current]
+                        .saturating_add(min(min(energy[lower], energy[left]), energy[right]));
                 }
             }
         }
     }
 
     /// Finds the column at the row `border` with the smallest energy.
-    pub fn calculate_min_energy_column(energy: &DMatrix<u32>, border: usize) -> usize {
+    pub fn calculate_min_energy_column(energy: &DMatrix<u64>, border: usize) -> usize {
         let mut column: usize = 0;
         for i in 1..border {
             if energy[(energy.nrows() - 1, column)] > energy[(energy.nrows() - 1, i)] {
@@ -138,7 +148,7 @@ pub mod energy {
     }
 
     /// Finds the row at the column `border` with the smallest energy.
-    pub fn calculate_min_energy_row(energy: &DMatrix<u32>, border: usize) -> usize {
+    pub fn calculate_min_energy_row(energy: &DMatrix<u64>, border: usize) -> usize {
         let mut row: usize = 0;
         for i in 1..border {
             if energy[(row, energy.ncols() - 1)] > energy[(i, energy.ncols() - 1)] {
@@ -162,14 +172,13 @@ pub mod energy {
     /// # Return
     ///  the vertical seam
     pub fn calculate_optimal_vertical_path(
-        energy: &DMatrix<u32>,
+        energy: &DMatrix<u64>,
         border: usize,
         start: usize,
     ) -> Vec<usize> {
         let mut seam = vec![0; energy.nrows()];
         seam[energy.nrows() - 1] = start;
         for j in (1..energy.nrows()).rev() {
-            let left = (j - 1, seam[j] - 1);
             let above = (j - 1, seam[j]);
             let right = (j - 1, seam[j] + 1);
             if seam[j] == 0 {
@@ -179,7 +188,10 @@ pub mod energy {
                 } else {
                     seam[j - 1] = seam[j] + 1;
                 }
-            } else if seam[j] == border - 1 {
+                continue;
+            }
+            let left = (j - 1, seam[j] - 1);
+            if seam[j] == border - 1 {
                 // Case: Right Border
                 if energy[above] <= energy[left] {
                     seam[j - 1] = seam[j];
@@ -227,14 +239,13 @@ pub mod energy {
     /// # Return
     ///  the horizontal seam
     pub fn calculate_optimal_horizontal_path(
-        energy: &DMatrix<u32>,
+        energy: &DMatrix<u64>,
         border: usize,
         start: usize,
     ) -> Vec<usize> {
         let mut seam = vec![0; energy.ncols()];
         seam[energy.ncols() - 1] = start;
         for j in (1..energy.ncols()).rev() {
-            let left = (seam[j] - 1, j - 1);
             let above = (seam[j], j - 1);
             let right = (seam[j] + 1, j - 1);
             if seam[j] == 0 {
@@ -244,7 +255,10 @@ pub mod energy {
                 } else {
                     seam[j - 1] = seam[j] + 1;
                 }
-            } else if seam[j] == border - 1 {
+                continue;
+            }
+            let left = (seam[j] - 1, j - 1);
+            if seam[j] == border - 1 {
                 // Case: Right Border
                 if energy[above] <= energy[left] {
                     seam[j - 1] = seam[j];
@@ -277,4 +291,58 @@ pub mod energy {
         }
         seam
     }
+
+    /// Carves a vertical seam, by shifting the pixels right of the seam one column to the left.
+    ///
+    /// # Parameters
+    ///  `pixels` - the pixel matrix
+    ///  `border` - the width up to which the energy matrix is calculated to
+    ///  `seam` - the seam to carve
+    pub fn carve_vertical_path<P: Copy>(pixels: &mut DMatrix<P>, border: usize, seam: &[usize]) {
+        for j in 0..pixels.nrows() {
+            let col = *seam.get(j).unwrap();
+            for i in col..border - 1 {
+                pixels[(j, i)] = pixels[(j, i + 1)];
+            }
+        }
+    }
+
+    /// Carves a horizontal seam, by shifting the pixels below the seam one row up.
+    ///
+    /// # Parameters
+    ///  `pixels` - the pixel matrix
+    ///  `border` - the height up to which the energy matrix is calculated to
+    ///  `seam` - the seam to carve
+    pub fn carve_horizontal_path<P: Copy>(pixels: &mut DMatrix<P>, border: usize, seam: &[usize]) {
+        for j in 0..pixels.ncols() {
+            let row = *seam.get(j).unwrap();
+            for i in row..border - 1 {
+                pixels[(i, j)] = pixels[(i + 1, j)];
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::pixel_utils::pixel::FloatPixel;
+
+        #[test]
+        fn saturates_large_color_differences() {
+            let samples = [0.0, f32::MAX, f32::INFINITY, f32::NAN, -f32::MAX, 1e30];
+            let pixels = DMatrix::from_fn(6, 6, |y, x| FloatPixel::from_gray(samples[(x + y) % 6]));
+            let mut energy = DMatrix::from_element(6, 6, 0);
+            calculate_vertical_energy_matrix(&pixels, &mut energy, 6);
+            assert!(energy.iter().any(|&energy| energy == u64::MAX));
+            let start = calculate_min_energy_column(&energy, 6);
+            assert_eq!(calculate_optimal_vertical_path(&energy, 6, start).len(), 6);
+            calculate_horizontal_energy_matrix(&pixels, &mut energy, 6);
+            assert!(energy.iter().any(|&energy| energy == u64::MAX));
+            let start = calculate_min_energy_row(&energy, 6);
+            assert_eq!(
+                calculate_optimal_horizontal_path(&energy, 6, start).len(),
+                6
+            );
+        }
+    }
 }
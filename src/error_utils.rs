@@ -0,0 +1,100 @@
+/// This crate contains the errors of reading, writing and manipulating images, so that a bad
+/// image can be reported instead of ending the program.
+pub mod error {
+    use crate::header_utils::header::HeaderError;
+    use std::{error, fmt, io};
+
+    /// The reasons why an image cannot be read, written or manipulated.
+    #[derive(Debug)]
+    pub enum ImageError {
+        /// The file or stream could not be read or written.
+        Io(io::Error),
+        /// The contents are not a valid image, e.g. because of a broken header.
+        Format(String),
+        /// The size of the image is invalid, or does not match its pixel data.
+        Dimensions(String),
+        /// A position lies outside of an image.
+        OutOfBounds {
+            /// The column of the position
+            x: usize,
+            /// The row of the position
+            y: usize,
+            /// The width of the image
+            width: usize,
+            /// The height of the image
+            height: usize,
+        },
+        /// The image or the operation is valid, but not supported.
+        Unsupported(String),
+    }
+
+    impl fmt::Display for ImageError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                ImageError::Io(err) => write!(f, "{err}"),
+                ImageError::Format(message)
+                | ImageError::Dimensions(message)
+                | ImageError::Unsupported(message) => write!(f, "{message}"),
+                ImageError::OutOfBounds {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => write!(
+                    f,
+                    "Position ({x}, {y}) is outside of the image of {width}x{height} pixels"
+                ),
+            }
+        }
+    }
+
+    impl error::Error for ImageError {
+        fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+            match self {
+                ImageError::Io(err) => Some(err),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<io::Error> for ImageError {
+        fn from(err: io::Error) -> ImageError {
+            ImageError::Io(err)
+        }
+    }
+
+    impl From<HeaderError> for ImageError {
+        fn from(err: HeaderError) -> ImageError {
+            ImageError::Format(err.to_string())
+        }
+    }
+
+    impl From<::png::DecodingError> for ImageError {
+        fn from(err: ::png::DecodingError) -> ImageError {
+            match err {
+                ::png::DecodingError::IoError(err) => ImageError::Io(err),
+                ::png::DecodingError::LimitsExceeded => ImageError::Dimensions(err.to_string()),
+                _ => ImageError::Format(err.to_string()),
+            }
+        }
+    }
+
+    impl From<::png::EncodingError> for ImageError {
+        fn from(err: ::png::EncodingError) -> ImageError {
+            match err {
+                ::png::EncodingError::IoError(err) => ImageError::Io(err),
+                ::png::EncodingError::LimitsExceeded => ImageError::Dimensions(err.to_string()),
+                _ => ImageError::Unsupported(err.to_string()),
+            }
+        }
+    }
+
+    impl From<::gif::EncodingError> for ImageError {
+        fn from(err: ::gif::EncodingError) -> ImageError {
+            match err {
+                ::gif::EncodingError::Io(err) => ImageError::Io(err),
+                _ => ImageError::Unsupported(err.to_string()),
+            }
+        }
+    }
+}
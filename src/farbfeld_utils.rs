@@ -0,0 +1,66 @@
+/// This crate contains the conversion between images and the farbfeld format of the suckless
+/// tools, which stores the red, green, blue and alpha samples of each pixel with 16 bits in
+/// big-endian order.
+pub mod farbfeld {
+    use crate::error_utils::error::ImageError;
+    use crate::header_utils::header::TupleType;
+    use crate::image_utils::image::{Format, Image};
+
+    /// The size of the header, which starts with "farbfeld".
+    const HEADER_SIZE: usize = 16;
+
+    /// Decodes a farbfeld file. The image gets the magic number P7, as it has an alpha channel.
+    ///
+    /// # Parameters:
+    ///  `contents` - The raw bytes of the file
+    ///
+    /// # Returns:
+    ///  `Result<Image, ImageError>` - The image or the reason why it could not be decoded
+    pub fn decode(contents: &[u8]) -> Result<Image, ImageError> {
+        if contents.len() < HEADER_SIZE || !contents.starts_with(b"farbfeld") {
+            return Err(ImageError::Format("Missing farbfeld signature".to_string()));
+        }
+        let width = u32::from_be_bytes(contents[8..12].try_into().unwrap()) as usize;
+        let height = u32::from_be_bytes(contents[12..16].try_into().unwrap()) as usize;
+        if width == 0 || height == 0 {
+            return Err(ImageError::Dimensions(
+                "Invalid farbfeld dimensions".to_string(),
+            ));
+        }
+        let pixels =
+            Image::parse_binary_pixels(&contents[HEADER_SIZE..], width, height, 4, u16::MAX)?;
+        Ok(Image {
+            format: Format::Farbfeld,
+            magic_number: "P7".to_string(),
+            scale: u16::MAX,
+            comments: Vec::new(),
+            tuple_type: Some(TupleType {
+                name: "RGB_ALPHA".to_string(),
+                depth: 4,
+            }),
+            pixels,
+        })
+    }
+
+    /// Encodes an image as farbfeld file. Images without alpha channel are stored as opaque.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to encode
+    ///
+    /// # Returns:
+    ///  `Result<Vec<u8>, ImageError>` - The contents of the file, or the reason why it could
+    ///  not be encoded
+    pub fn encode(image: &Image) -> Result<Vec<u8>, ImageError> {
+        let (height, width) = image.pixels.shape();
+        let to_u32 = |value: usize| {
+            u32::try_from(value)
+                .map_err(|_| ImageError::Dimensions("Image too large for farbfeld".to_string()))
+        };
+        let mut contents = Vec::with_capacity(HEADER_SIZE + width * height * 8);
+        contents.extend_from_slice(b"farbfeld");
+        contents.extend_from_slice(&to_u32(width)?.to_be_bytes());
+        contents.extend_from_slice(&to_u32(height)?.to_be_bytes());
+        contents.extend_from_slice(&image.binary_pixels(4, u16::MAX));
+        Ok(contents)
+    }
+}
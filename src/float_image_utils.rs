@@ -0,0 +1,192 @@
+/// This crate contains the images of floating point pixels, which represent high dynamic range
+/// images, and their conversion to and from images with 8 or 16 bits per sample.
+pub mod float_image {
+    use crate::error_utils::error::ImageError;
+    use crate::image_utils::image::{Format, Image};
+    use crate::pfm_utils::pfm;
+    use crate::pixel_utils::pixel::{self, FloatPixel, ImagePixel, Pixel};
+    use std::io::Write;
+
+    /// The curves that map the samples of high dynamic range images to the range of the display.
+    #[derive(Clone, Copy, PartialEq, Debug, Default)]
+    #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+    pub enum ToneMapping {
+        /// Cuts off everything brighter than white
+        #[default]
+        Clamp,
+        /// Compresses highlights with `x / (1 + x)`
+        Reinhard,
+        /// The filmic curve of the Academy Color Encoding System
+        Aces,
+    }
+
+    impl ToneMapping {
+        /// Maps a linear sample to the range `0.0..=1.0`.
+        ///
+        /// # Parameters:
+        ///  `sample` - The linear sample, which may exceed 1.0
+        pub fn apply(self, sample: f32) -> f32 {
+            let sample = sample.max(0.0);
+            let mapped = match self {
+                ToneMapping::Clamp => sample,
+                ToneMapping::Reinhard => sample / (1.0 + sample),
+                ToneMapping::Aces => {
+                    (sample * (2.51 * sample + 0.03)) / (sample * (2.43 * sample + 0.59) + 0.14)
+                }
+            };
+            // Infinite samples divide infinity by infinity, but all curves approach white.
+            if mapped.is_nan() {
+                return 1.0;
+            }
+            mapped.clamp(0.0, 1.0)
+        }
+    }
+
+    /// High dynamic range images, e.g. rendered scenes, whose pixels hold linear light as floating
+    /// point numbers. They have the magic number PF for color and Pf for grayscale images, which
+    /// are written with a single sample per pixel, and a scale of 65535, like the 16-bit pixels
+    /// they are converted to.
+    pub type FloatImage = Image<FloatPixel>;
+
+    impl Image<FloatPixel> {
+        //=== CONVERSION ==========================================================================
+
+        /// Returns a floating point image with the colors of an image, converted from the sRGB
+        /// curve to linear light. The alpha channel is dropped.
+        ///
+        /// # Parameters:
+        ///  `image` - The image to convert
+        pub fn from_image(image: &Image) -> FloatImage {
+            FloatImage {
+                format: Format::Pfm,
+                magic_number: if image.channels() <= 2 { "Pf" } else { "PF" }.to_string(),
+                scale: u16::MAX,
+                comments: Vec::new(),
+                tuple_type: None,
+                pixels: image.pixels.map(FloatPixel::from_pixel),
+            }
+        }
+
+        /// Converts the image to an opaque image with 8 bits per sample. The samples are mapped
+        /// to the range of the display with a tone mapping curve, and then converted to the sRGB
+        /// curve.
+        ///
+        /// # Parameters:
+        ///  `tone_mapping` - The curve that maps the samples to the range of the display
+        ///
+        /// # Returns:
+        ///  `Image` - The image, with the magic number P5 for grayscale and P6 for color images
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        pub fn to_image(&self, tone_mapping: ToneMapping) -> Image {
+            let sample = |sample: f32| {
+                let value = pixel::linear_to_srgb(tone_mapping.apply(sample));
+                (value * 255.0).round() as u16
+            };
+            Image {
+                format: Format::Pfm,
+                magic_number: if self.channels() == 1 { "P5" } else { "P6" }.to_string(),
+                scale: 255,
+                comments: Vec::new(),
+                tuple_type: None,
+                pixels: self.pixels.map(|pixel| {
+                    Pixel {
+                        red: sample(pixel.red),
+                        green: sample(pixel.green),
+                        blue: sample(pixel.blue),
+                        alpha: 255,
+                    }
+                    .normalize(255)
+                }),
+            }
+        }
+
+        //=== READING & WRITING ===================================================================
+
+        /// Write an image to a stream as PFM file.
+        ///
+        /// # Parameters:
+        ///  `writer` - the stream to write the image file to
+        pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), ImageError> {
+            writer.write_all(&pfm::encode(self))?;
+            writer.flush()?;
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use nalgebra::DMatrix;
+
+        /// Returns a floating point image of 2 by 3 pixels with distinct gray values.
+        fn image() -> FloatImage {
+            let pixels = DMatrix::from_row_slice(2, 3, &[0.0, 0.25, 0.5, 1.0, 2.0, 4.0]);
+            FloatImage {
+                format: Format::Pfm,
+                magic_number: "Pf".to_string(),
+                scale: u16::MAX,
+                comments: Vec::new(),
+                tuple_type: None,
+                pixels: pixels.map(FloatPixel::from_gray),
+            }
+        }
+
+        #[test]
+        fn maps_samples_to_the_display() {
+            assert_eq!(ToneMapping::Clamp.apply(0.5), 0.5);
+            assert_eq!(ToneMapping::Clamp.apply(4.0), 1.0);
+            assert_eq!(ToneMapping::Reinhard.apply(1.0), 0.5);
+            assert!((ToneMapping::Aces.apply(1.0) - 0.8).abs() < 0.01);
+            for tone_mapping in [ToneMapping::Clamp, ToneMapping::Reinhard, ToneMapping::Aces] {
+                assert_eq!(tone_mapping.apply(-1.0), 0.0);
+                assert_eq!(tone_mapping.apply(f32::NAN), 0.0);
+                assert_eq!(tone_mapping.apply(f32::INFINITY), 1.0);
+                assert!(tone_mapping.apply(1e6) > 0.99);
+            }
+        }
+
+        #[test]
+        fn converts_to_opaque_images_and_back() {
+            let image = image().to_image(ToneMapping::Clamp);
+            assert_eq!(image.magic_number, "P5");
+            let grays: Vec<u16> = image.pixels.iter().map(|pixel| pixel.red / 257).collect();
+            assert_eq!(grays, [0, 255, 137, 255, 188, 255]);
+            assert!(image.pixels.iter().all(|pixel| pixel.alpha == u16::MAX));
+            let float = FloatImage::from_image(&image);
+            assert_eq!(float.magic_number, "Pf");
+            assert_eq!(float.to_image(ToneMapping::Clamp).pixels, image.pixels);
+        }
+
+        #[test]
+        fn transforms_floating_point_samples() {
+            let image = image();
+            let gray = |image: &FloatImage, y: usize, x: usize| image.pixels[(y, x)].red;
+            assert_eq!(gray(&image.mirror(), 0, 0), 0.5);
+            assert_eq!(gray(&image.transpose(), 2, 1), 4.0);
+            assert_eq!(gray(&image.rotate(), 0, 0), 1.0);
+            assert_eq!(gray(&image.crop(1, 3, 1, 2).unwrap(), 0, 1), 4.0);
+            let mut inverted = image.crop(0, 3, 0, 2).unwrap();
+            inverted.invert();
+            assert_eq!(gray(&inverted, 0, 1), 0.75);
+            assert_eq!(gray(&inverted, 1, 2), 0.0);
+            let mut carved = image.crop(0, 3, 0, 2).unwrap();
+            carved.pixels[(0, 1)] = FloatPixel::from_gray(f32::INFINITY);
+            carved.seam_carve(1, true, None).unwrap();
+            assert_eq!(carved.pixels.shape(), (2, 2));
+        }
+
+        #[test]
+        fn landfills_floating_point_samples() {
+            let mut image = image();
+            image.pixels[(0, 1)] = FloatPixel::from_gray(0.0);
+            image
+                .landfill((1, 0), (u16::MAX, u16::MAX, u16::MAX))
+                .unwrap();
+            let grays: Vec<f32> = image.pixels.iter().map(|pixel| pixel.red).collect();
+            assert_eq!(grays, [1.0, 1.0, 1.0, 2.0, 0.5, 4.0]);
+            image.tuple_type = None;
+            image.magic_number = "P7".to_string();
+            assert_eq!(image.channels(), FloatPixel::CHANNELS);
+        }
+    }
+}
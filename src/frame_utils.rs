@@ -0,0 +1,567 @@
+/// This crate contains the reading and writing of streams with several images. Netpbm images and
+/// Portable FloatMaps may be concatenated in one file or stream, where each image is called a
+/// frame. Streams in other formats hold a single frame, except for raw pixel data, where frames of
+/// the same size follow each other.
+pub mod frame {
+    use crate::error_utils::error::ImageError;
+    use crate::float_image_utils::float_image::{FloatImage, ToneMapping};
+    use crate::image_utils::image::{Format, Image};
+    use crate::pfm_utils::pfm;
+    use crate::raw_utils::raw::RawLayout;
+    use crate::terminal_utils::terminal;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// The number of bytes that are read at once when the length of a frame is not known yet.
+    const CHUNK_SIZE: usize = 1 << 16;
+
+    /// An iterator over the frames of a stream, which are read and decoded one after the other,
+    /// so that a stream of Netpbm, PFM or raw frames is never held in memory at once. Raw frames
+    /// need their width, height and layout, given with `with_raw_layout`. Portable FloatMaps are
+    /// converted with a tone mapping, given with `with_tone_mapping`, or read with their
+    /// floating point samples by `next_float`.
+    pub struct Frames {
+        reader: Box<dyn Read>,
+        buffer: Vec<u8>,
+        scanned: (usize, usize),
+        finished: bool,
+        file: Option<String>,
+        format: Format,
+        raw: Option<(usize, usize, RawLayout)>,
+        tone_mapping: ToneMapping,
+    }
+
+    impl Frames {
+        /// Creates an iterator over the frames of a file, or of the standard input if the file
+        /// is `-`.
+        ///
+        /// # Parameters:
+        ///  `file` - The location of the file
+        ///  `format` - The format of the file, detected from its first bytes if not given
+        ///
+        /// # Returns:
+        ///  `Result<Frames, ImageError>` - The iterator, or the reason why the file cannot be
+        ///  read or its format is unknown
+        pub fn read(file: &str, format: Option<Format>) -> Result<Frames, ImageError> {
+            let result = if file == "-" {
+                Self::read_from(io::stdin().lock(), format)
+            } else {
+                File::open(file)
+                    .map_err(ImageError::from)
+                    .and_then(|reader| Self::read_from(reader, format))
+            };
+            let mut frames = result.map_err(|err| read_error(file, err))?;
+            frames.file = Some(file.to_string());
+            Ok(frames)
+        }
+
+        /// Creates an iterator over the frames of a stream. Only the first bytes are read, to
+        /// detect the format.
+        ///
+        /// # Parameters:
+        ///  `reader` - The stream that contains the frames
+        ///  `format` - The format of the stream, detected from its first bytes if not given
+        ///
+        /// # Returns:
+        ///  `Result<Frames, ImageError>` - The iterator, or the reason why the stream cannot be
+        ///  read or its format is unknown
+        pub fn read_from<R: Read + 'static>(
+            reader: R,
+            format: Option<Format>,
+        ) -> Result<Frames, ImageError> {
+            let mut reader: Box<dyn Read> = Box::new(reader);
+            let mut buffer = Vec::new();
+            let format = match format {
+                Some(format) => format,
+                None => {
+                    // The longest signature is the one of farbfeld images.
+                    reader.by_ref().take(8).read_to_end(&mut buffer)?;
+                    Format::detect(&buffer).ok_or_else(|| {
+                        ImageError::Format(
+                            "Unknown image format, expected a Netpbm, PNG, BMP, QOI, farbfeld or PFM file"
+                                .to_string(),
+                        )
+                    })?
+                }
+            };
+            Ok(Frames {
+                reader,
+                buffer,
+                scanned: (0, 0),
+                finished: false,
+                file: None,
+                format,
+                raw: None,
+                tone_mapping: ToneMapping::default(),
+            })
+        }
+
+        /// Sets the size and layout of raw frames, which have no header.
+        ///
+        /// # Parameters:
+        ///  `width` - The width of each frame
+        ///  `height` - The height of each frame
+        ///  `layout` - The order, bit depth and endianness of the samples
+        pub fn with_raw_layout(mut self, width: usize, height: usize, layout: RawLayout) -> Frames {
+            self.raw = Some((width, height, layout));
+            self
+        }
+
+        /// Sets the tone mapping of Portable FloatMaps, which are converted to 8 bits per sample.
+        ///
+        /// # Parameters:
+        ///  `tone_mapping` - The curve that maps the samples to the range of the display
+        pub fn with_tone_mapping(mut self, tone_mapping: ToneMapping) -> Frames {
+            self.tone_mapping = tone_mapping;
+            self
+        }
+
+        /// Returns the format of the frames.
+        pub fn format(&self) -> Format {
+            self.format
+        }
+
+        /// Decodes the next frame of a Portable FloatMap stream, keeping its floating point
+        /// samples. After an invalid frame, the stream ends.
+        pub fn next_float(&mut self) -> Option<Result<FloatImage, ImageError>> {
+            if self.format != Format::Pfm {
+                if self.finished && self.buffer.is_empty() {
+                    return None;
+                }
+                self.finish();
+                return Some(Err(ImageError::Unsupported(
+                    "Only PFM frames have floating point samples".to_string(),
+                )));
+            }
+            self.decode_next(pfm::decode)
+        }
+
+        /// Reads the next frame and decodes it. After an invalid frame, the stream ends.
+        ///
+        /// # Parameters:
+        ///  `decode` - Decodes a frame at the start of the buffer, and returns it with the number
+        ///  of bytes it occupies
+        fn decode_next<T>(
+            &mut self,
+            decode: impl FnOnce(&[u8]) -> Result<(T, usize), ImageError>,
+        ) -> Option<Result<T, ImageError>> {
+            match self.fill() {
+                Ok(false) => return None,
+                Ok(true) => {}
+                Err(err) => {
+                    self.finish();
+                    return Some(Err(err));
+                }
+            }
+            let result = decode(&self.buffer);
+            match &result {
+                Ok((_, length)) => {
+                    self.buffer.drain(..*length);
+                    self.scanned = (0, 0);
+                }
+                Err(_) => self.finish(),
+            }
+            Some(result.map(|(frame, _)| frame))
+        }
+
+        /// Reads from the stream until the buffer holds the next frame, or the stream ends. The
+        /// whitespace between Netpbm and PFM frames is skipped. Streams in formats without
+        /// several frames are read to their end.
+        ///
+        /// # Returns:
+        ///  `Result<bool, ImageError>` - Whether there is another frame, or the reason why the
+        ///  stream cannot be read
+        fn fill(&mut self) -> Result<bool, ImageError> {
+            loop {
+                self.skip_whitespace();
+                if self.buffer.is_empty() {
+                    if self.finished {
+                        return Ok(false);
+                    }
+                    self.read_chunk()?;
+                    continue;
+                }
+                if self.finished {
+                    return Ok(true);
+                }
+                let length = match (self.format, self.raw) {
+                    (Format::Netpbm, _) => Image::netpbm_length(&self.buffer, &mut self.scanned),
+                    (Format::Pfm, _) => pfm::length(&self.buffer),
+                    (Format::Raw, Some((width, height, layout))) => {
+                        layout.frame_size(width, height).map(Some)
+                    }
+                    _ => Ok(None),
+                };
+                match length {
+                    // Invalid frames are left to the decoder, which reports why.
+                    Err(_) => return Ok(true),
+                    Ok(Some(length)) if length <= self.buffer.len() => return Ok(true),
+                    Ok(Some(length)) => self.read_up_to(length)?,
+                    Ok(None) => self.read_chunk()?,
+                }
+            }
+        }
+
+        /// Reads from the stream until the buffer holds `length` bytes, or the stream ends.
+        fn read_up_to(&mut self, length: usize) -> Result<(), ImageError> {
+            let missing = length.saturating_sub(self.buffer.len()) as u64;
+            let count = (&mut self.reader)
+                .take(missing)
+                .read_to_end(&mut self.buffer)
+                .map_err(|err| self.io_error(err))?;
+            self.finished = (count as u64) < missing;
+            Ok(())
+        }
+
+        /// Reads the bytes that are available from the stream, up to `CHUNK_SIZE`.
+        fn read_chunk(&mut self) -> Result<(), ImageError> {
+            let start = self.buffer.len();
+            self.buffer.resize(start + CHUNK_SIZE, 0);
+            let count = loop {
+                match self.reader.read(&mut self.buffer[start..]) {
+                    Ok(count) => break count,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(err) => {
+                        self.buffer.truncate(start);
+                        return Err(self.io_error(err));
+                    }
+                }
+            };
+            self.buffer.truncate(start + count);
+            self.finished = count == 0;
+            Ok(())
+        }
+
+        /// Adds the file to an error that occurred while reading it.
+        fn io_error(&self, err: io::Error) -> ImageError {
+            match &self.file {
+                Some(file) => read_error(file, ImageError::Io(err)),
+                None => ImageError::Io(err),
+            }
+        }
+
+        /// Ends the stream, e.g. after an invalid frame.
+        fn finish(&mut self) {
+            self.buffer.clear();
+            self.finished = true;
+        }
+
+        /// Skips whitespace between Netpbm and PFM frames.
+        fn skip_whitespace(&mut self) {
+            if matches!(self.format, Format::Netpbm | Format::Pfm) {
+                let count = self
+                    .buffer
+                    .iter()
+                    .take_while(|byte| byte.is_ascii_whitespace())
+                    .count();
+                if count > 0 {
+                    self.buffer.drain(..count);
+                    self.scanned = (0, 0);
+                }
+            }
+        }
+    }
+
+    /// Adds the file to an error that occurred while reading it.
+    ///
+    /// # Parameters:
+    ///  `file` - The location of the file
+    ///  `err` - The error
+    fn read_error(file: &str, err: ImageError) -> ImageError {
+        match err {
+            ImageError::Io(err) => ImageError::Io(io::Error::new(
+                err.kind(),
+                format!("Could not read {file}: {err}"),
+            )),
+            err => err,
+        }
+    }
+
+    impl Iterator for Frames {
+        type Item = Result<Image, ImageError>;
+
+        /// Reads and decodes the next frame. Whitespace between Netpbm and PFM frames is
+        /// skipped, and the stream ends after an invalid frame.
+        fn next(&mut self) -> Option<Result<Image, ImageError>> {
+            let format = self.format;
+            match (format, self.raw) {
+                (Format::Netpbm, _) => self.decode_next(Image::decode_netpbm),
+                (Format::Pfm, _) => {
+                    let tone_mapping = self.tone_mapping;
+                    self.next_float()
+                        .map(|result| result.map(|image| image.to_image(tone_mapping)))
+                }
+                (Format::Raw, Some((width, height, layout))) => self.decode_next(|contents| {
+                    Image::from_raw(contents, width, height, &layout)
+                        .and_then(|image| Ok((image, layout.frame_size(width, height)?)))
+                }),
+                _ => self.decode_next(|contents| {
+                    Image::decode_as(contents, format).map(|image| (image, contents.len()))
+                }),
+            }
+        }
+    }
+
+    /// A writer that appends frames to a stream. Only Netpbm, PFM and raw frames can follow each
+    /// other, so streams in other formats are limited to a single frame. The frames of a file are
+    /// written to a temporary file next to it, which replaces the file in `finish`, and is
+    /// removed if the writer is dropped before.
+    pub struct FrameWriter {
+        writer: Box<dyn Write>,
+        frames: usize,
+        preview: Option<(Box<dyn Write>, bool)>,
+        raw: Option<RawLayout>,
+        file: Option<(PathBuf, PathBuf)>,
+    }
+
+    impl FrameWriter {
+        /// Creates a writer for a stream.
+        ///
+        /// # Parameters:
+        ///  `writer` - The stream to append the frames to
+        pub fn new<W: Write + 'static>(writer: W) -> FrameWriter {
+            FrameWriter {
+                writer: Box::new(writer),
+                frames: 0,
+                preview: None,
+                raw: None,
+                file: None,
+            }
+        }
+
+        /// Creates a writer for a file, or for the standard output if the file is `-`. An
+        /// existing file is only replaced in `finish`, so that it can be read while its
+        /// replacement is written, e.g. if it is the input, and it stays untouched if writing
+        /// fails.
+        ///
+        /// # Parameters:
+        ///  `filename` - The location of the file
+        pub fn create(filename: &str) -> io::Result<FrameWriter> {
+            if filename == "-" {
+                return Ok(Self::new(io::stdout().lock()));
+            }
+            // Devices and pipes cannot be replaced, so they are written directly.
+            let target = match fs::canonicalize(filename) {
+                Ok(path) if !path.is_file() => return Ok(Self::new(File::create(filename)?)),
+                Ok(path) => path,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => PathBuf::from(filename),
+                Err(err) => return Err(err),
+            };
+            let (temporary, file) = temporary_file(&target)?;
+            let mut writer = Self::new(file);
+            writer.file = Some((temporary, target));
+            Ok(writer)
+        }
+
+        /// Prints a preview of every appended frame to the terminal.
+        ///
+        /// # Parameters:
+        ///  `writer` - The stream the previews are printed to
+        ///  `sixel` - Whether the previews are printed as sixel graphics
+        pub fn preview_to<W: Write + 'static>(&mut self, writer: W, sixel: bool) {
+            self.preview = Some((Box::new(writer), sixel));
+        }
+
+        /// Sets the layout of raw frames. Without a layout, raw frames keep all their samples.
+        ///
+        /// # Parameters:
+        ///  `layout` - The order, bit depth and endianness of the samples
+        pub fn set_raw_layout(&mut self, layout: RawLayout) {
+            self.raw = Some(layout);
+        }
+
+        /// Appends a frame to the stream.
+        ///
+        /// # Parameters:
+        ///  `image` - The frame to append
+        pub fn append(&mut self, image: &Image) -> Result<(), ImageError> {
+            if self.frames > 0
+                && !matches!(image.format, Format::Netpbm | Format::Pfm | Format::Raw)
+            {
+                return Err(ImageError::Unsupported(
+                    "Only Netpbm, PFM and raw streams can hold several images".to_string(),
+                ));
+            }
+            match (image.format, self.raw) {
+                (Format::Raw, Some(layout)) => {
+                    self.writer.write_all(&image.to_raw(&layout)?)?;
+                    self.writer.flush()?;
+                }
+                _ => image.write_to(&mut self.writer)?,
+            }
+            self.frames += 1;
+            if let Some((preview, sixel)) = &mut self.preview {
+                terminal::preview(image, preview, *sixel)?;
+            }
+            Ok(())
+        }
+
+        /// Appends a floating point frame to a Portable FloatMap stream. Its preview is clamped
+        /// to the range of the display.
+        ///
+        /// # Parameters:
+        ///  `image` - The frame to append
+        pub fn append_float(&mut self, image: &FloatImage) -> Result<(), ImageError> {
+            image.write_to(&mut self.writer)?;
+            self.frames += 1;
+            if let Some((preview, sixel)) = &mut self.preview {
+                terminal::preview(&image.to_image(ToneMapping::default()), preview, *sixel)?;
+            }
+            Ok(())
+        }
+
+        /// Finishes the stream. The frames of a file replace the previous file only now.
+        pub fn finish(mut self) -> Result<(), ImageError> {
+            self.writer.flush()?;
+            if let Some((temporary, target)) = self.file.take() {
+                // The temporary file is closed before it is moved.
+                self.writer = Box::new(io::sink());
+                if let Err(err) = fs::rename(&temporary, &target) {
+                    let _ = fs::remove_file(&temporary);
+                    return Err(err.into());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for FrameWriter {
+        /// Removes the temporary file of a stream that was not finished.
+        fn drop(&mut self) {
+            if let Some((temporary, _)) = self.file.take() {
+                self.writer = Box::new(io::sink());
+                let _ = fs::remove_file(temporary);
+            }
+        }
+    }
+
+    /// Creates a temporary file next to a file, with a name that no other writer uses. It gets
+    /// the permissions of the file, if the file exists.
+    ///
+    /// # Parameters:
+    ///  `target` - The location of the file
+    ///
+    /// # Returns:
+    ///  `io::Result<(PathBuf, File)>` - The location of the temporary file and the file itself
+    fn temporary_file(target: &Path) -> io::Result<(PathBuf, File)> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        loop {
+            let count = COUNT.fetch_add(1, Ordering::Relaxed);
+            let path = target.with_file_name(format!(".{name}.{}-{count}.tmp", process::id()));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    if let Ok(metadata) = fs::metadata(target) {
+                        file.set_permissions(metadata.permissions())?;
+                    }
+                    return Ok((path, file));
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// A stream that hands out its contents in pieces of at most `piece` bytes, and fails
+        /// when it is read beyond them, like a pipe whose writer has not written more yet.
+        struct Stream {
+            contents: Vec<u8>,
+            piece: usize,
+        }
+
+        impl Read for Stream {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.contents.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::WouldBlock, "not written yet"));
+                }
+                let count = buf.len().min(self.piece).min(self.contents.len());
+                buf[..count].copy_from_slice(&self.contents[..count]);
+                self.contents.drain(..count);
+                Ok(count)
+            }
+        }
+
+        /// Returns the frames of a stream with the given contents.
+        fn frames(contents: &[u8], piece: usize) -> Frames {
+            let contents = contents.to_vec();
+            Frames::read_from(Stream { contents, piece }, None).unwrap()
+        }
+
+        #[test]
+        fn decodes_frames_before_the_stream_continues() {
+            let mut pfm = b"PF\n1 1\n-1.0\n".to_vec();
+            pfm.extend(
+                [0.5f32, 1.0, 0.0]
+                    .iter()
+                    .flat_map(|sample| sample.to_le_bytes()),
+            );
+            for contents in [
+                &b"P6 1 1 255 abc"[..],
+                b"P3 1 1 255 1 2 3\n",
+                b"P1 3 1 101",
+                &pfm,
+            ] {
+                for piece in [1, 7, CHUNK_SIZE] {
+                    let image = frames(contents, piece).next().unwrap().unwrap();
+                    assert_eq!(image.pixels.nrows(), 1);
+                }
+            }
+        }
+
+        #[test]
+        fn reads_concatenated_frames() {
+            let contents = b"P1 2 1 10\nP2 1 1 255 7\n\nP6 1 1 255 abc\n";
+            for piece in [1, 3, CHUNK_SIZE] {
+                let mut frames = frames(contents, piece);
+                for magic_number in ["P1", "P2", "P6"] {
+                    let image = frames.next().unwrap().unwrap();
+                    assert_eq!(image.magic_number, magic_number);
+                }
+                // The stream fails when it is read again.
+                assert!(frames.next().unwrap().is_err());
+            }
+        }
+
+        #[test]
+        fn ends_after_an_invalid_frame() {
+            let contents = b"P6 1 1 255 abcP6 x".to_vec();
+            let mut frames = Frames::read_from(io::Cursor::new(contents), None).unwrap();
+            assert!(frames.next().unwrap().is_ok());
+            assert!(frames.next().unwrap().is_err());
+            assert!(frames.next().is_none());
+        }
+
+        #[test]
+        fn replaces_files_once_they_are_finished() {
+            let directory = std::env::temp_dir().join(format!("simp-frames-{}", process::id()));
+            fs::create_dir_all(&directory).unwrap();
+            let file = directory.join("image.ppm");
+            let file = file.to_str().unwrap();
+            fs::write(file, b"P3 2 1 255 1 2 3 4 5 6\n").unwrap();
+            // A writer that is dropped before it is finished leaves the file untouched.
+            let mut output = FrameWriter::create(file).unwrap();
+            output
+                .append(&Frames::read(file, None).unwrap().next().unwrap().unwrap())
+                .unwrap();
+            drop(output);
+            assert_eq!(fs::read(file).unwrap(), b"P3 2 1 255 1 2 3 4 5 6\n");
+            // The file can be read while it is replaced.
+            let mut output = FrameWriter::create(file).unwrap();
+            for image in Frames::read(file, None).unwrap() {
+                output.append(&image.unwrap().mirror()).unwrap();
+            }
+            output.finish().unwrap();
+            let image = Frames::read(file, None).unwrap().next().unwrap().unwrap();
+            assert_eq!(image.pixels[(0, 0)].red, 4 * 257);
+            assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+            fs::remove_dir_all(directory).unwrap();
+        }
+    }
+}
@@ -0,0 +1,70 @@
+/// This crate contains the recording of images as animated Graphics Interchange Format (GIF)
+/// files, e.g. to watch the steps of seam carving. The palette quantization and the LZW
+/// compression are done by the `gif` crate.
+pub mod gif {
+    use crate::image_utils::image::Image;
+    use ::gif::{DisposalMethod, Encoder, EncodingError, Frame, Repeat};
+    use std::fs::File;
+    use std::io::{self, Write};
+
+    /// The speed of the palette quantization, between 1 (best quality) and 30 (fastest).
+    const QUANTIZATION_SPEED: i32 = 10;
+
+    /// An animation that is encoded frame by frame. The size of the animation is the size of its
+    /// first frame, and the animation repeats endlessly.
+    pub struct Animation {
+        writer: Option<Box<dyn Write>>,
+        encoder: Option<Encoder<Box<dyn Write>>>,
+        delay: u16,
+    }
+
+    impl Animation {
+        /// Creates an animation that is written to a stream.
+        ///
+        /// # Parameters:
+        ///  `writer` - The stream to write the GIF file to
+        ///  `delay` - The time each frame is shown, in hundredths of a second
+        pub fn new<W: Write + 'static>(writer: W, delay: u16) -> Animation {
+            Animation {
+                writer: Some(Box::new(writer)),
+                encoder: None,
+                delay,
+            }
+        }
+
+        /// Creates an animation that is written to a file.
+        ///
+        /// # Parameters:
+        ///  `filename` - The location of the file
+        ///  `delay` - The time each frame is shown, in hundredths of a second
+        pub fn create(filename: &str, delay: u16) -> io::Result<Animation> {
+            Ok(Self::new(File::create(filename)?, delay))
+        }
+
+        /// Appends an image as frame to the animation. Its colors are reduced to a palette of
+        /// 256 colors, and fully transparent pixels stay transparent. Frames are cleared before
+        /// the next one is shown, so that smaller frames do not show parts of their predecessors.
+        ///
+        /// # Parameters:
+        ///  `image` - The image to append
+        pub fn add_frame(&mut self, image: &Image) -> Result<(), EncodingError> {
+            let too_large = || io::Error::other("Image too large for GIF");
+            let width = u16::try_from(image.pixels.ncols()).map_err(|_| too_large())?;
+            let height = u16::try_from(image.pixels.nrows()).map_err(|_| too_large())?;
+            let encoder = match &mut self.encoder {
+                Some(encoder) => encoder,
+                None => {
+                    let writer = self.writer.take().ok_or(EncodingError::WriterNotFound)?;
+                    let mut encoder = Encoder::new(writer, width, height, &[])?;
+                    encoder.set_repeat(Repeat::Infinite)?;
+                    self.encoder.insert(encoder)
+                }
+            };
+            let mut rgba = image.binary_pixels(4, 255);
+            let mut frame = Frame::from_rgba_speed(width, height, &mut rgba, QUANTIZATION_SPEED);
+            frame.delay = self.delay;
+            frame.dispose = DisposalMethod::Background;
+            encoder.write_frame(&frame)
+        }
+    }
+}
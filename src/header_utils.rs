@@ -0,0 +1,417 @@
+/// This crate contains a tokenizing parser for the headers of Netpbm images, following the Netpbm
+/// grammar: tokens are separated by arbitrary whitespace, and comments run from a `#` to the end of
+/// the line. Headers of Portable Arbitrary Maps (P7) consist of lines with a keyword and a value.
+pub mod header {
+    use std::fmt;
+
+    /// The largest width and height of an image, so that it fits into the 32-bit headers of the
+    /// other formats.
+    const MAX_DIMENSION: usize = u32::MAX as usize;
+
+    /// The header of a Netpbm image. Portable Bitmaps have no scale in their header, their
+    /// `scale` is set to 1. The `comments` are stored without the leading `#`, so that they can
+    /// be written again. Only Portable Arbitrary Maps have a `tuple_type`.
+    #[derive(Debug)]
+    pub struct Header {
+        /// The magic number, P1 to P7
+        pub magic_number: String,
+        /// The width in pixels
+        pub width: usize,
+        /// The height in pixels
+        pub height: usize,
+        /// The maximum value of each sample
+        pub scale: u16,
+        /// The comments, without the leading `#`
+        pub comments: Vec<String>,
+        /// The tuple type of Portable Arbitrary Maps
+        pub tuple_type: Option<TupleType>,
+    }
+
+    /// The tuple type of a Portable Arbitrary Map, i.e. the `name` of the kind of samples and the
+    /// `depth`, which is the number of samples per pixel. A depth of 1 stands for gray, 2 for gray
+    /// and alpha, 3 for red, green and blue, and 4 for red, green, blue and alpha samples.
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct TupleType {
+        /// The name of the kind of samples, e.g. RGB_ALPHA
+        pub name: String,
+        /// The number of samples per pixel
+        pub depth: usize,
+    }
+
+    impl TupleType {
+        /// Returns the usual tuple type of a depth, e.g. RGB for a depth of 3.
+        ///
+        /// # Parameters:
+        ///  `depth` - the number of samples per pixel
+        pub fn with_depth(depth: usize) -> TupleType {
+            let name = match depth {
+                1 => "GRAYSCALE",
+                2 => "GRAYSCALE_ALPHA",
+                3 => "RGB",
+                _ => "RGB_ALPHA",
+            };
+            TupleType {
+                name: name.to_string(),
+                depth,
+            }
+        }
+    }
+
+    /// An error in a header, with the line and column (both starting at 1) where it occurred.
+    /// The header is `truncated` if the error occurred at the end of the data, so that it may be
+    /// complete once more of a stream has been read.
+    #[derive(Debug)]
+    pub struct HeaderError {
+        /// The line of the error, starting at 1
+        pub line: usize,
+        /// The column of the error, starting at 1
+        pub column: usize,
+        /// The description of the error
+        pub message: String,
+        /// Whether the error occurred at the end of the data
+        pub truncated: bool,
+    }
+
+    impl fmt::Display for HeaderError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                f,
+                "Invalid header at line {}, column {}: {}",
+                self.line, self.column, self.message
+            )
+        }
+    }
+
+    /// Splits the header into tokens, while keeping track of the current line and column and
+    /// collecting the comments.
+    struct Tokenizer<'a> {
+        data: &'a [u8],
+        position: usize,
+        line: usize,
+        column: usize,
+        comments: Vec<String>,
+    }
+
+    impl<'a> Tokenizer<'a> {
+        /// Creates a tokenizer at the beginning of `data`.
+        fn new(data: &'a [u8]) -> Tokenizer<'a> {
+            Tokenizer {
+                data,
+                position: 0,
+                line: 1,
+                column: 1,
+                comments: Vec::new(),
+            }
+        }
+
+        /// Returns an error at the current position.
+        fn error(&self, message: String) -> HeaderError {
+            HeaderError {
+                line: self.line,
+                column: self.column,
+                message,
+                truncated: self.position >= self.data.len(),
+            }
+        }
+
+        /// Consumes a single byte and updates the line and column.
+        fn advance(&mut self) -> u8 {
+            let byte = self.data[self.position];
+            self.position += 1;
+            if byte == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            byte
+        }
+
+        /// Skips whitespace and comments. Comments are collected without the `#` and a single
+        /// leading space.
+        fn skip_whitespace(&mut self) {
+            while let Some(&byte) = self.data.get(self.position) {
+                if byte == b'#' {
+                    self.advance();
+                    let start = self.position;
+                    while self.data.get(self.position).is_some_and(|&b| b != b'\n') {
+                        self.advance();
+                    }
+                    let comment = String::from_utf8_lossy(&self.data[start..self.position]);
+                    let comment = comment.strip_prefix(' ').unwrap_or(&comment);
+                    self.comments
+                        .push(comment.trim_end_matches('\r').to_string());
+                } else if byte.is_ascii_whitespace() {
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+        }
+
+        /// Returns the magic number, which are the first two bytes of the file.
+        fn magic_number(&mut self) -> Result<String, HeaderError> {
+            match self.data.get(..2) {
+                Some([b'P', digit]) if (b'1'..=b'7').contains(digit) => {
+                    self.advance();
+                    self.advance();
+                    Ok(format!("P{}", char::from(*digit)))
+                }
+                _ => Err(HeaderError {
+                    truncated: self.data.len() < 2,
+                    ..self.error("Unsupported magic number".to_string())
+                }),
+            }
+        }
+
+        /// Parses the next token as a decimal number between `min` and `max`.
+        ///
+        /// # Parameters:
+        ///  `name` - the name of the value, used in error messages
+        ///  `min` - the smallest allowed value
+        ///  `max` - the largest allowed value
+        fn number(&mut self, name: &str, min: usize, max: usize) -> Result<usize, HeaderError> {
+            self.skip_whitespace();
+            let (line, column) = (self.line, self.column);
+            let start = self.position;
+            while self.data.get(self.position).is_some_and(u8::is_ascii_digit) {
+                self.advance();
+            }
+            let token = String::from_utf8_lossy(&self.data[start..self.position]);
+            let error = |message: String| HeaderError {
+                line,
+                column,
+                message,
+                truncated: false,
+            };
+            if token.is_empty() {
+                return Err(match self.data.get(self.position) {
+                    Some(&byte) => error(format!("Expected {name}, found '{}'", char::from(byte))),
+                    None => HeaderError {
+                        truncated: true,
+                        ..error(format!("Expected {name}, found end of file"))
+                    },
+                });
+            }
+            match token.parse::<usize>() {
+                Ok(value) if (min..=max).contains(&value) => Ok(value),
+                _ => Err(error(format!(
+                    "The {name} {token} is not between {min} and {max}"
+                ))),
+            }
+        }
+
+        /// Returns the next token, which ends at whitespace or at a comment.
+        fn word(&mut self) -> String {
+            self.skip_whitespace();
+            let start = self.position;
+            while self
+                .data
+                .get(self.position)
+                .is_some_and(|&b| !b.is_ascii_whitespace() && b != b'#')
+            {
+                self.advance();
+            }
+            String::from_utf8_lossy(&self.data[start..self.position]).to_string()
+        }
+
+        /// Returns the rest of the current line, without surrounding whitespace.
+        fn rest_of_line(&mut self) -> String {
+            let start = self.position;
+            while self.data.get(self.position).is_some_and(|&b| b != b'\n') {
+                self.advance();
+            }
+            String::from_utf8_lossy(&self.data[start..self.position])
+                .trim()
+                .to_string()
+        }
+
+        /// Consumes the single whitespace character that separates the header from the pixels.
+        fn end(&mut self) -> Result<(), HeaderError> {
+            match self.data.get(self.position) {
+                Some(byte) if byte.is_ascii_whitespace() => {
+                    self.advance();
+                    Ok(())
+                }
+                Some(&byte) => Err(self.error(format!(
+                    "Expected whitespace after the header, found '{}'",
+                    char::from(byte)
+                ))),
+                None => Err(self.error("Expected pixels, found end of file".to_string())),
+            }
+        }
+    }
+
+    /// Parses the header of a Netpbm image file.
+    ///
+    /// # Parameters:
+    ///  `contents` - the raw bytes of the file
+    ///
+    /// # Returns:
+    ///  `Result<(Header, usize), HeaderError>` - The header and the position where the pixels
+    ///  start, or the position and reason of the error
+    pub fn parse(contents: &[u8]) -> Result<(Header, usize), HeaderError> {
+        let mut tokenizer = Tokenizer::new(contents);
+        let magic_number = tokenizer.magic_number()?;
+        if magic_number == "P7" {
+            return parse_arbitrary_map(tokenizer);
+        }
+        let width = tokenizer.number("width", 1, MAX_DIMENSION)?;
+        let height = tokenizer.number("height", 1, MAX_DIMENSION)?;
+        let scale = if magic_number == "P1" || magic_number == "P4" {
+            1
+        } else {
+            let scale = tokenizer.number("scale", 1, usize::from(u16::MAX))?;
+            u16::try_from(scale).unwrap()
+        };
+        tokenizer.end()?;
+        let header = Header {
+            magic_number,
+            width,
+            height,
+            scale,
+            comments: tokenizer.comments,
+            tuple_type: None,
+        };
+        Ok((header, tokenizer.position))
+    }
+
+    /// Parses the header of a Portable Arbitrary Map after the magic number. The header consists
+    /// of the lines WIDTH, HEIGHT, DEPTH, MAXVAL and any number of TUPLTYPE lines, in any order,
+    /// and ends with ENDHDR. Tuple types that are not known are accepted, as long as their depth
+    /// is between 1 and 4.
+    ///
+    /// # Parameters:
+    ///  `tokenizer` - the tokenizer, positioned after the magic number
+    ///
+    /// # Returns:
+    ///  `Result<(Header, usize), HeaderError>` - The header and the position where the pixels
+    ///  start, or the position and reason of the error
+    fn parse_arbitrary_map(mut tokenizer: Tokenizer) -> Result<(Header, usize), HeaderError> {
+        let (mut width, mut height, mut depth, mut scale) = (None, None, None, None);
+        let mut names: Vec<String> = Vec::new();
+        loop {
+            tokenizer.skip_whitespace();
+            let (line, column) = (tokenizer.line, tokenizer.column);
+            let keyword = tokenizer.word();
+            match keyword.as_str() {
+                "WIDTH" => width = Some(tokenizer.number("width", 1, MAX_DIMENSION)?),
+                "HEIGHT" => height = Some(tokenizer.number("height", 1, MAX_DIMENSION)?),
+                "DEPTH" => depth = Some(tokenizer.number("depth", 1, 4)?),
+                "MAXVAL" => scale = Some(tokenizer.number("scale", 1, usize::from(u16::MAX))?),
+                "TUPLTYPE" => names.push(tokenizer.rest_of_line()),
+                "ENDHDR" => break,
+                "" => return Err(tokenizer.error("Expected ENDHDR, found end of file".into())),
+                _ => {
+                    return Err(HeaderError {
+                        line,
+                        column,
+                        message: format!("Unknown keyword {keyword}"),
+                        truncated: tokenizer.position >= tokenizer.data.len(),
+                    })
+                }
+            }
+        }
+        tokenizer.end()?;
+        let missing = |name: &str| tokenizer.error(format!("Missing {name} before ENDHDR"));
+        let width = width.ok_or_else(|| missing("WIDTH"))?;
+        let height = height.ok_or_else(|| missing("HEIGHT"))?;
+        let depth = depth.ok_or_else(|| missing("DEPTH"))?;
+        let scale = scale.ok_or_else(|| missing("MAXVAL"))?;
+        let mut tuple_type = TupleType::with_depth(depth);
+        if !names.is_empty() {
+            tuple_type.name = names.join(" ");
+        }
+        let header = Header {
+            magic_number: "P7".to_string(),
+            width,
+            height,
+            scale: u16::try_from(scale).unwrap(),
+            comments: tokenizer.comments,
+            tuple_type: Some(tuple_type),
+        };
+        Ok((header, tokenizer.position))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parses_comments_and_whitespace() {
+            let contents = b"P2 # first\n 3\t# second\r\n\n2 #\n255\nrest";
+            let (header, offset) = parse(contents).unwrap();
+            assert_eq!(header.magic_number, "P2");
+            assert_eq!((header.width, header.height, header.scale), (3, 2, 255));
+            assert_eq!(header.comments, ["first", "second", ""]);
+            assert_eq!(&contents[offset..], b"rest");
+        }
+
+        #[test]
+        fn bitmaps_have_no_scale() {
+            let (header, offset) = parse(b"P4\n# comment\n9 1\n\xff\x80").unwrap();
+            assert_eq!((header.width, header.height, header.scale), (9, 1, 1));
+            assert_eq!(header.comments, ["comment"]);
+            assert_eq!(offset, 17);
+        }
+
+        #[test]
+        fn parses_arbitrary_maps() {
+            let contents = b"P7\nTUPLTYPE RGB_ALPHA\n# c\nMAXVAL 65535\nDEPTH 4\nHEIGHT 1\n\
+                             WIDTH 2\nENDHDR\n";
+            let (header, offset) = parse(contents).unwrap();
+            assert_eq!(
+                (header.width, header.height, header.scale),
+                (2, 1, u16::MAX)
+            );
+            assert_eq!(
+                header.tuple_type,
+                Some(TupleType {
+                    name: "RGB_ALPHA".to_string(),
+                    depth: 4
+                })
+            );
+            assert_eq!(header.comments, ["c"]);
+            assert_eq!(offset, contents.len());
+        }
+
+        #[test]
+        fn arbitrary_maps_get_a_default_tuple_type() {
+            let (header, _) = parse(b"P7 WIDTH 1 HEIGHT 1 DEPTH 2 MAXVAL 1 ENDHDR\n").unwrap();
+            assert_eq!(header.tuple_type.unwrap().name, "GRAYSCALE_ALPHA");
+        }
+
+        #[test]
+        fn reports_the_position_of_errors() {
+            let error = parse(b"P3\n# comment\n3 x\n255\n").unwrap_err();
+            assert_eq!((error.line, error.column), (3, 3));
+            assert_eq!(error.message, "Expected height, found 'x'");
+        }
+
+        #[test]
+        fn rejects_malformed_headers() {
+            for contents in [
+                &b""[..],
+                b"P8\n1 1\n255\n",
+                b"P6\n1 1\n255",
+                b"P6\n1 1\n255x",
+                b"P6\n0 1\n255\n",
+                b"P6\n1 1\n0\n",
+                b"P6\n1 1\n65536\n",
+                b"P6\n1 -1\n255\n",
+                b"P6\n4294967296 1\n255\n",
+                b"P6\n1 99999999999999999999999\n255\n",
+                b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 5\nMAXVAL 255\nENDHDR\n",
+                b"P7\nWIDTH 1\nHEIGHT 1\nMAXVAL 255\nENDHDR\n",
+                b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nMAXVAL 255\n",
+                b"P7\nWIDTH 1\nSIZE 1\nENDHDR\n",
+            ] {
+                assert!(
+                    parse(contents).is_err(),
+                    "{}",
+                    String::from_utf8_lossy(contents)
+                );
+            }
+        }
+    }
+}
@@ -1,93 +1,372 @@
 /// This crate contains the data structure that represents images as pixel matrices and
 /// functionalities as cropping, rotating, inverting and seam carving.
-
 pub mod image {
+    use crate::bmp_utils::bmp;
     use crate::energy_utils::energy;
-    use crate::pixel_utils::pixel::Pixel;
+    use crate::error_utils::error::ImageError;
+    use crate::farbfeld_utils::farbfeld;
+    use crate::float_image_utils::float_image::{FloatImage, ToneMapping};
+    use crate::gif_utils::gif::Animation;
+    use crate::header_utils::header::{self, Header, TupleType};
+    use crate::pfm_utils::pfm;
+    use crate::pixel_utils::pixel::{self, ImagePixel, Pixel};
+    use crate::png_utils::png;
+    use crate::qoi_utils::qoi;
+    use crate::raw_utils::raw::{self, RawLayout};
     use nalgebra::DMatrix;
     use std::borrow::Cow;
     use std::fmt::Write as OtherWrite;
-    use std::fs;
     use std::io::Write;
+    use std::path::Path;
+
+    /// Netpbm images store their pixel values either as ASCII text or as raw bytes.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+    pub enum Encoding {
+        /// Samples as decimal numbers, e.g. P3
+        Ascii,
+        /// Samples as raw bytes, e.g. P6
+        Binary,
+    }
+
+    /// The file formats that images are read from and written to.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+    pub enum Format {
+        /// Portable Bitmaps, Graymaps, Pixmaps and Arbitrary Maps (P1 to P7)
+        Netpbm,
+        /// Portable Network Graphics
+        Png,
+        /// Windows bitmaps
+        Bmp,
+        /// The Quite OK Image format
+        Qoi,
+        /// The farbfeld format of 16-bit RGBA samples
+        Farbfeld,
+        /// Portable FloatMaps of floating point samples
+        Pfm,
+        /// Raw pixel data without header
+        Raw,
+    }
+
+    impl Format {
+        /// Returns the format that belongs to the extension of a file name, if it is known.
+        ///
+        /// # Parameters:
+        ///  `filename` - the name of the file
+        pub fn from_extension(filename: &str) -> Option<Format> {
+            let extension = Path::new(filename).extension()?.to_str()?;
+            match extension.to_ascii_lowercase().as_str() {
+                "pbm" | "pgm" | "ppm" | "pnm" | "pam" => Some(Format::Netpbm),
+                "png" => Some(Format::Png),
+                "bmp" => Some(Format::Bmp),
+                "qoi" => Some(Format::Qoi),
+                "ff" => Some(Format::Farbfeld),
+                "pfm" => Some(Format::Pfm),
+                "raw" => Some(Format::Raw),
+                _ => None,
+            }
+        }
 
-    /// Images in the PPM format have a `magic_number`, e.g. P3 for Portable Pixmaps (ASCII), and a
-    /// `scale` is the maximum value for each color. Images are represented as pixel matrices, here
-    /// in `pixels`.
-    pub struct Image {
+        /// Returns the format of a file, detected from its first bytes: the magic numbers P1 to
+        /// P7 of Netpbm images, the PNG signature, "BM" for BMP, "qoif" for QOI, "farbfeld" for
+        /// farbfeld images, and PF or Pf for Portable FloatMaps.
+        ///
+        /// # Parameters:
+        ///  `contents` - the raw bytes of the file
+        pub fn detect(contents: &[u8]) -> Option<Format> {
+            match contents {
+                [b'P', b'1'..=b'7', ..] => Some(Format::Netpbm),
+                [b'P', b'F' | b'f', ..] => Some(Format::Pfm),
+                [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some(Format::Png),
+                [b'B', b'M', ..] => Some(Format::Bmp),
+                [b'q', b'o', b'i', b'f', ..] => Some(Format::Qoi),
+                [b'f', b'a', b'r', b'b', b'f', b'e', b'l', b'd', ..] => Some(Format::Farbfeld),
+                _ => None,
+            }
+        }
+    }
+
+    /// Images in the Netpbm formats have a `magic_number`, e.g. P3 for Portable Pixmaps (ASCII), P5
+    /// for Portable Graymaps (binary) or P1 for Portable Bitmaps (ASCII), and a `scale` is the
+    /// maximum value for each color. The `comments` of the header are kept and written again.
+    /// Images are represented as pixel matrices, here in `pixels`. Graymaps and bitmaps are stored
+    /// as gray pixels, i.e. with equal colors, so that all manipulations work on them as well.
+    /// Portable Arbitrary Maps (P7) additionally have a `tuple_type`, and may have an alpha
+    /// channel, which is kept in the pixels. The `format` is the file format the image is written
+    /// in. Images in other formats have the magic number of the closest Netpbm format, e.g. P7 for
+    /// PNG images with alpha channel.
+    ///
+    /// The pixels are 16-bit `Pixel`s by default, but may be any kind of `ImagePixel`, e.g. the
+    /// floating point pixels of `FloatImage`. Transforms and seam carving work on every kind of
+    /// pixel, reading and writing most formats only on `Pixel`s.
+    pub struct Image<P = Pixel> {
+        /// The file format the image is written in
+        pub format: Format,
+        /// The magic number of the closest Netpbm format
         pub magic_number: String,
-        pub scale: u8,
-        pub pixels: DMatrix<Pixel>,
+        /// The maximum value of each sample in the file
+        pub scale: u16,
+        /// The comments of the header
+        pub comments: Vec<String>,
+        /// The tuple type of Portable Arbitrary Maps
+        pub tuple_type: Option<TupleType>,
+        /// The pixel matrix, with a row per line of the image
+        pub pixels: DMatrix<P>,
     }
 
     impl Image {
         //=== READING & WRITING ===================================================================
 
-        /// Returns an image struct, parsed from a file
+        /// Returns an image struct, parsed from the contents of a file in the given format.
+        /// Portable FloatMaps are clamped to the range of the display.
         ///
         /// # Parameters:
-        ///  `file` - The location of the file, as a String
+        ///  `contents` - The raw bytes of the file
+        ///  `format` - The format of the file
         ///
         /// # Returns:
-        ///  `Image` - Representation of the image file with the struct Image
-        pub fn read(file: &String) -> Image {
-            let contents = match fs::read_to_string(file) {
-                Ok(str) => str,
-                Err(err) => panic!("{err:?}"),
-            };
-            let mut lines = contents.lines();
-            let header: Vec<&str> = lines.by_ref().take(3).collect();
-            let body_str: Vec<String> = lines
+        ///  `Result<Image, ImageError>` - Representation of the image file with the struct Image,
+        ///  or the reason why it could not be decoded
+        pub fn decode_as(contents: &[u8], format: Format) -> Result<Image, ImageError> {
+            match format {
+                Format::Netpbm => Ok(Self::decode_netpbm(contents)?.0),
+                Format::Png => png::decode(contents),
+                Format::Bmp => bmp::decode(contents),
+                Format::Qoi => qoi::decode(contents),
+                Format::Farbfeld => farbfeld::decode(contents),
+                Format::Pfm => Ok(pfm::decode(contents)?.0.to_image(ToneMapping::default())),
+                Format::Raw => Err(ImageError::Unsupported(
+                    "Raw images need a size and a layout".to_string(),
+                )),
+            }
+        }
+
+        /// Returns an image struct, parsed from raw pixel data without header.
+        ///
+        /// # Parameters:
+        ///  `data` - The raw pixel data
+        ///  `width` - The width of the image
+        ///  `height` - The height of the image
+        ///  `layout` - The order, bit depth and endianness of the samples
+        ///
+        /// # Returns:
+        ///  `Result<Image, ImageError>` - Representation of the image with the struct Image, or
+        ///  the reason why it could not be decoded
+        pub fn from_raw(
+            data: &[u8],
+            width: usize,
+            height: usize,
+            layout: &RawLayout,
+        ) -> Result<Image, ImageError> {
+            raw::decode(data, width, height, layout)
+        }
+
+        /// Encodes the pixels as raw pixel data without header.
+        ///
+        /// # Parameters:
+        ///  `layout` - The order, bit depth and endianness of the samples
+        ///
+        /// # Returns:
+        ///  `Result<Vec<u8>, ImageError>` - The raw pixel data, or the reason why it could not be
+        ///  encoded
+        pub fn to_raw(&self, layout: &RawLayout) -> Result<Vec<u8>, ImageError> {
+            raw::encode(self, layout)
+        }
+
+        /// Returns an image struct, parsed from the contents of a Netpbm file. The contents may
+        /// continue after the image, e.g. with further images of a stream.
+        ///
+        /// # Parameters:
+        ///  `contents` - The raw bytes of the file
+        ///
+        /// # Returns:
+        ///  `Result<(Image, usize), ImageError>` - Representation of the image file with the
+        ///  struct Image and the number of bytes it occupies, or the reason why it could not be
+        ///  decoded
+        pub fn decode_netpbm(contents: &[u8]) -> Result<(Image, usize), ImageError> {
+            let (header, offset) = header::parse(contents)?;
+            let channels = Self::header_channels(&header);
+            let body_bytes = &contents[offset..];
+            let length = Self::body_length(&header, body_bytes)?;
+            let body_bytes = &body_bytes[..length.min(body_bytes.len())];
+            let Header {
+                magic_number,
+                width,
+                height,
+                scale,
+                comments,
+                tuple_type,
+            } = header;
+            let body_str: Vec<String> = String::from_utf8_lossy(body_bytes)
+                .lines()
                 .map(|line| Cow::<str>::Owned(line.replace('\n', " ")).into_owned())
                 .collect();
             let body: Vec<&str> = body_str.iter().map(std::string::String::as_str).collect();
-            let Some((magic_number, width, height, scale)) = Self::parse_header(&header) else {
-                panic!("Error in parsing the header")
-            };
-            let pixels: DMatrix<Pixel> = match Self::parse_pixels(&body, width, height) {
-                Ok(pixels) => pixels,
-                Err(e) => panic!("{e:?}"),
-            };
-            Image {
+            let pixels = match magic_number.as_str() {
+                "P1" => Self::parse_bits(&body, width, height),
+                "P2" | "P3" => Self::parse_pixels(&body, width, height, channels, scale),
+                "P4" => Self::parse_binary_bits(body_bytes, width, height),
+                "P5" | "P6" | "P7" => {
+                    Self::parse_binary_pixels(body_bytes, width, height, channels, scale)
+                }
+                _ => Err(ImageError::Unsupported(format!(
+                    "Unsupported magic number: {magic_number}"
+                ))),
+            }?;
+            let image = Image {
+                format: Format::Netpbm,
                 magic_number,
                 scale,
+                comments,
+                tuple_type,
                 pixels,
-            }
+            };
+            Ok((image, offset + body_bytes.len()))
         }
 
-        /// Parse the header of a PPM image file.
+        /// Returns the number of bytes of the Netpbm image at the start of `contents`, so that a
+        /// stream can be read one image at a time. Binary images end after the size given by
+        /// their header, ASCII images after their last sample, which needs to be followed by
+        /// another byte, since it could continue otherwise.
         ///
         /// # Parameters:
-        ///  `lines` - The lines to parse
+        ///  `contents` - The bytes of the stream that have been read so far
+        ///  `scanned` - The position in the body and the number of samples where the scan of
+        ///  an ASCII image stopped, so that the next call on more of the image continues there
         ///
         /// # Returns:
-        ///  `Option<(String, usize, usize, u8)>` - Parse the magic number and the dimensions of the file.
-        fn parse_header(lines: &[&str]) -> Option<(String, usize, usize, u8)> {
-            let magic_number = lines.first().unwrap();
-            let dimensions: Vec<&str> = match lines.get(1) {
-                Some(dimensions) => dimensions.split(' ').collect(),
-                None => return None,
+        ///  `Result<Option<usize>, ImageError>` - The length, `None` if the contents end before
+        ///  the length is known, or the reason why the header is invalid
+        pub(crate) fn netpbm_length(
+            contents: &[u8],
+            scanned: &mut (usize, usize),
+        ) -> Result<Option<usize>, ImageError> {
+            let (header, offset) = match header::parse(contents) {
+                Ok(header) => header,
+                Err(err) if err.truncated => return Ok(None),
+                Err(err) => return Err(err.into()),
             };
-            let width = dimensions[0].parse::<usize>().unwrap();
-            let height = dimensions[1].parse::<usize>().unwrap();
-            let scale: u8 = match lines.get(2) {
-                Some(size) => size.parse::<u8>().unwrap(),
-                None => return None,
+            let body = &contents[offset..];
+            let bits = match header.magic_number.as_str() {
+                "P1" => true,
+                "P2" | "P3" => false,
+                _ => return Ok(Some(offset + Self::body_length(&header, body)?)),
             };
-            Some(((*magic_number).to_string(), width, height, scale))
+            let channels = Self::header_channels(&header);
+            let count = Self::data_size(&[header.width, header.height, channels])?;
+            *scanned = Self::ascii_scan(body, count, bits, *scanned);
+            Ok((scanned.1 == count).then_some(offset + scanned.0))
         }
 
-        /// Parse the pixels of the PPM image file.
+        /// Returns the number of samples per pixel of a Netpbm image: 3 for pixmaps, the depth
+        /// of the tuple type for Portable Arbitrary Maps, and 1 otherwise.
+        fn header_channels(header: &Header) -> usize {
+            match header.magic_number.as_str() {
+                "P3" | "P6" => 3,
+                "P7" => header
+                    .tuple_type
+                    .as_ref()
+                    .map_or(4, |tuple_type| tuple_type.depth),
+                _ => 1,
+            }
+        }
+
+        /// Returns the number of bytes of the pixels that follow the header. Binary bodies have
+        /// the size given by the header, ASCII bodies end after their last sample, or at the end
+        /// of `body` if it holds fewer samples.
         ///
         /// # Parameters:
-        ///  lines - The lines to parse
+        ///  `header` - The header of the image
+        ///  `body` - The bytes following the header
+        fn body_length(header: &Header, body: &[u8]) -> Result<usize, ImageError> {
+            let (width, height) = (header.width, header.height);
+            let samples = Self::data_size(&[width, height, Self::header_channels(header)])?;
+            Ok(match header.magic_number.as_str() {
+                "P1" => Self::ascii_length(body, samples, true),
+                "P2" | "P3" => Self::ascii_length(body, samples, false),
+                "P4" => Self::data_size(&[width.div_ceil(8), height])?,
+                _ => Self::data_size(&[samples, Self::sample_bytes(header.scale)])?,
+            })
+        }
+
+        /// Returns the number of bytes that hold the first `count` samples of an ASCII body.
+        /// Samples are separated by whitespace, except for bits, which are single characters.
+        ///
+        /// # Parameters:
+        ///  `data` - The bytes following the header
+        ///  `count` - The number of samples of the image
+        ///  `bits` - Whether the samples are bits of a Portable Bitmap
+        fn ascii_length(data: &[u8], count: usize, bits: bool) -> usize {
+            match Self::ascii_scan(data, count, bits, (0, 0)) {
+                (position, samples) if samples == count => position,
+                _ => data.len(),
+            }
+        }
+
+        /// Scans the samples of an ASCII body, until `count` samples are found. A sample at the
+        /// end of the data is not counted, since it could continue, except for bits.
+        ///
+        /// # Parameters:
+        ///  `data` - The bytes following the header
+        ///  `count` - The number of samples of the image
+        ///  `bits` - Whether the samples are bits of a Portable Bitmap
+        ///  `start` - The position and the number of samples where a previous scan stopped
+        ///
+        /// # Returns:
+        ///  `(usize, usize)` - The position after the last sample, and the number of samples
+        fn ascii_scan(
+            data: &[u8],
+            count: usize,
+            bits: bool,
+            (mut position, mut samples): (usize, usize),
+        ) -> (usize, usize) {
+            let is_whitespace = |byte: &&u8| byte.is_ascii_whitespace();
+            while samples < count {
+                let start = position + data[position..].iter().take_while(is_whitespace).count();
+                let end = if bits {
+                    start + 1
+                } else {
+                    start
+                        + data[start..]
+                            .iter()
+                            .take_while(|b| !is_whitespace(b))
+                            .count()
+                };
+                if end > data.len() || (!bits && end == data.len()) {
+                    return (start, samples);
+                }
+                position = end;
+                samples += 1;
+            }
+            (position, samples)
+        }
+
+        /// Returns true for the magic numbers of Portable Bitmaps (P1 and P4), which have no
+        /// scale in their header.
+        fn is_bitmap(magic_number: &str) -> bool {
+            magic_number == "P1" || magic_number == "P4"
+        }
+
+        /// Parse the pixels of an ASCII PPM or PGM image file. The samples are normalized
+        /// against the scale.
+        ///
+        /// # Parameters:
+        ///  `lines` - The lines to parse
+        ///  `width` - The width of the image
+        ///  `height` - The height of the image
+        ///  `channels` - The number of values per pixel, 1 for gray and 3 for rgb
+        ///  `scale` - The maximum value of each color
         ///
         /// # Returns:
-        ///  `Option<Vec<Pixel>>`-  Returns an Optional of a pixel matrix, saved as vector
+        ///  `Result<DMatrix<Pixel>, ImageError>` - The pixel matrix or an error message
         fn parse_pixels(
             lines: &[&str],
             width: usize,
             height: usize,
-        ) -> Result<DMatrix<Pixel>, &'static str> {
+            channels: usize,
+            scale: u16,
+        ) -> Result<DMatrix<Pixel>, ImageError> {
             let data: String = lines
                 .iter()
                 .fold(String::new(), |mut acc, line| {
@@ -97,18 +376,41 @@ pub mod image {
                 .chars()
                 .collect();
             let values: Vec<&str> = data.split_whitespace().collect();
-            if values.len() < width * height * 3 {
-                println!("Insufficient data for the specified dimensions");
+            if values.len() < Self::data_size(&[width, height, channels])? {
+                return Err(ImageError::Dimensions(
+                    "Insufficient data for the specified dimensions".to_string(),
+                ));
             }
             let mut pixels = Vec::new();
-            for chunk in values.chunks(3) {
-                if let [r, g, b] = chunk {
-                    let red: u8 = r.parse().map_err(|_| "Failed to parse red component")?;
-                    let green: u8 = g.parse().map_err(|_| "Failed to parse green component")?;
-                    let blue: u8 = b.parse().map_err(|_| "Failed to parse blue component")?;
-                    pixels.push(Pixel { red, green, blue });
-                } else {
-                    return Err("Invalid pixel data");
+            for chunk in values.chunks(channels) {
+                match chunk {
+                    [v] => {
+                        let gray: u16 = v.parse().map_err(|_| {
+                            ImageError::Format("Failed to parse gray component".to_string())
+                        })?;
+                        pixels.push(Pixel {
+                            alpha: scale,
+                            ..Pixel::from_gray(gray)
+                        });
+                    }
+                    [r, g, b] => {
+                        let red: u16 = r.parse().map_err(|_| {
+                            ImageError::Format("Failed to parse red component".to_string())
+                        })?;
+                        let green: u16 = g.parse().map_err(|_| {
+                            ImageError::Format("Failed to parse green component".to_string())
+                        })?;
+                        let blue: u16 = b.parse().map_err(|_| {
+                            ImageError::Format("Failed to parse blue component".to_string())
+                        })?;
+                        pixels.push(Pixel {
+                            red,
+                            green,
+                            blue,
+                            alpha: scale,
+                        });
+                    }
+                    _ => return Err(ImageError::Format("Invalid pixel data".to_string())),
                 }
             }
             let mut matrix = DMatrix::zeros(height, width);
@@ -117,51 +419,415 @@ pub mod image {
                 let col = idx % width;
                 matrix[(row, col)] = pixel;
             }
-            Ok(matrix)
+            Self::normalize_pixels(matrix, scale)
         }
 
-        /// Write an image to a file.
+        /// Scales the samples of a pixel matrix from the range `0..=scale` to the full range
+        /// `0..=u16::MAX`.
         ///
         /// # Parameters:
-        ///  `filename` - path to the file
-        pub fn write(&self, filename: &String) {
-            let mut file = fs::File::create(filename).expect("Could not write to file");
-            writeln!(file, "{}", self.magic_number).expect("Could not write magic number.");
-            writeln!(file, "{} {}", self.pixels.ncols(), self.pixels.nrows())
-                .expect("Could not write height and width.");
-            writeln!(file, "{}", self.scale).expect("Could not write scale");
+        ///  `pixels` - The pixel matrix, as read from the file
+        ///  `scale` - The maximum value of each color
+        ///
+        /// # Returns:
+        ///  `Result<DMatrix<Pixel>, ImageError>` - The pixel matrix or an error message
+        fn normalize_pixels(
+            pixels: DMatrix<Pixel>,
+            scale: u16,
+        ) -> Result<DMatrix<Pixel>, ImageError> {
+            if pixels
+                .iter()
+                .any(|pixel| pixel.red > scale || pixel.green > scale || pixel.blue > scale)
+            {
+                return Err(ImageError::Format("Sample exceeds the scale".to_string()));
+            }
+            Ok(pixels.map(|pixel| pixel.normalize(scale)))
+        }
+
+        /// Parse the pixels of an ASCII PBM image file (P1). Each bit is a single character, a 1
+        /// stands for a black pixel and a 0 for a white pixel. Whitespace between bits is
+        /// optional.
+        ///
+        /// # Parameters:
+        ///  `lines` - The lines to parse
+        ///  `width` - The width of the image
+        ///  `height` - The height of the image
+        ///
+        /// # Returns:
+        ///  `Result<DMatrix<Pixel>, ImageError>` - The pixel matrix or an error message
+        fn parse_bits(
+            lines: &[&str],
+            width: usize,
+            height: usize,
+        ) -> Result<DMatrix<Pixel>, ImageError> {
+            let mut bits = Vec::new();
+            for c in lines.iter().flat_map(|line| line.chars()) {
+                match c {
+                    '0' => bits.push(false),
+                    '1' => bits.push(true),
+                    c if c.is_whitespace() => {}
+                    _ => return Err(ImageError::Format("Failed to parse bit".to_string())),
+                }
+            }
+            if bits.len() < Self::data_size(&[width, height])? {
+                return Err(ImageError::Dimensions(
+                    "Insufficient data for the specified dimensions".to_string(),
+                ));
+            }
+            Ok(DMatrix::from_fn(height, width, |row, col| {
+                Pixel::from_bit(bits[row * width + col])
+            }))
+        }
+
+        /// Parse the pixels of a binary PPM (P6), PGM (P5) or PAM (P7) image file, where each
+        /// sample is stored as a single byte, or as two bytes in big-endian order if the scale
+        /// exceeds 255. The samples are normalized against the scale.
+        ///
+        /// # Parameters:
+        ///  `data` - The bytes following the header
+        ///  `width` - The width of the image
+        ///  `height` - The height of the image
+        ///  `channels` - The number of samples per pixel, 1 for gray, 2 for gray and alpha, 3 for
+        ///  rgb and 4 for rgb and alpha
+        ///  `scale` - The maximum value of each color
+        ///
+        /// # Returns:
+        ///  `Result<DMatrix<Pixel>, ImageError>` - The pixel matrix or an error message
+        pub fn parse_binary_pixels(
+            data: &[u8],
+            width: usize,
+            height: usize,
+            channels: usize,
+            scale: u16,
+        ) -> Result<DMatrix<Pixel>, ImageError> {
+            let sample_bytes = Self::sample_bytes(scale);
+            if data.len() < Self::data_size(&[width, height, channels, sample_bytes])? {
+                return Err(ImageError::Dimensions(
+                    "Insufficient data for the specified dimensions".to_string(),
+                ));
+            }
+            let sample = |idx: usize| {
+                if sample_bytes == 2 {
+                    u16::from_be_bytes([data[2 * idx], data[2 * idx + 1]])
+                } else {
+                    u16::from(data[idx])
+                }
+            };
+            let pixels = DMatrix::from_fn(height, width, |row, col| {
+                let idx = (row * width + col) * channels;
+                match channels {
+                    1 => Pixel {
+                        alpha: scale,
+                        ..Pixel::from_gray(sample(idx))
+                    },
+                    2 => Pixel {
+                        alpha: sample(idx + 1),
+                        ..Pixel::from_gray(sample(idx))
+                    },
+                    3 => Pixel {
+                        red: sample(idx),
+                        green: sample(idx + 1),
+                        blue: sample(idx + 2),
+                        alpha: scale,
+                    },
+                    _ => Pixel {
+                        red: sample(idx),
+                        green: sample(idx + 1),
+                        blue: sample(idx + 2),
+                        alpha: sample(idx + 3),
+                    },
+                }
+            });
+            Self::normalize_pixels(pixels, scale)
+        }
+
+        /// Returns the number of bytes per sample in binary files: one byte for a scale of up to
+        /// 255, and two bytes otherwise.
+        fn sample_bytes(scale: u16) -> usize {
+            if scale > 255 {
+                2
+            } else {
+                1
+            }
+        }
+
+        /// Returns the number of samples or bytes of an image, i.e. the product of its dimensions,
+        /// e.g. the width, the height and the number of samples per pixel. The dimensions come
+        /// from the header of the file, so they are checked before anything is allocated.
+        ///
+        /// # Parameters:
+        ///  `dimensions` - The factors of the size
+        ///
+        /// # Returns:
+        ///  `Result<usize, ImageError>` - The size, or an error if it exceeds the address space
+        pub(crate) fn data_size(dimensions: &[usize]) -> Result<usize, ImageError> {
+            dimensions
+                .iter()
+                .try_fold(1, |size: usize, &dimension| size.checked_mul(dimension))
+                .ok_or_else(|| ImageError::Dimensions("The image is too large".to_string()))
+        }
+
+        /// Parse the pixels of a binary PBM image file (P4). The bits are packed into bytes, with
+        /// the most significant bit first, and each row starts at a new byte.
+        ///
+        /// # Parameters:
+        ///  `data` - The bytes following the header
+        ///  `width` - The width of the image
+        ///  `height` - The height of the image
+        ///
+        /// # Returns:
+        ///  `Result<DMatrix<Pixel>, ImageError>` - The pixel matrix or an error message
+        fn parse_binary_bits(
+            data: &[u8],
+            width: usize,
+            height: usize,
+        ) -> Result<DMatrix<Pixel>, ImageError> {
+            let row_bytes = width.div_ceil(8);
+            if data.len() < Self::data_size(&[row_bytes, height])? {
+                return Err(ImageError::Dimensions(
+                    "Insufficient data for the specified dimensions".to_string(),
+                ));
+            }
+            Ok(DMatrix::from_fn(height, width, |row, col| {
+                let byte = data[row * row_bytes + col / 8];
+                Pixel::from_bit(byte & (0x80 >> (col % 8)) != 0)
+            }))
+        }
+
+        /// Write an image to a stream, in the format of the image. Raw images keep all their
+        /// samples, see `RawLayout::of`, and Portable FloatMaps get linear samples, see
+        /// `FloatImage::from_image`.
+        ///
+        /// # Parameters:
+        ///  `writer` - the stream to write the image file to
+        pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), ImageError> {
+            let contents = match self.format {
+                Format::Netpbm => self.encode_netpbm()?,
+                Format::Png => png::encode(self)?,
+                Format::Bmp => bmp::encode(self)?,
+                Format::Qoi => qoi::encode(self)?,
+                Format::Farbfeld => farbfeld::encode(self)?,
+                Format::Pfm => pfm::encode(&FloatImage::from_image(self)),
+                Format::Raw => self.to_raw(&RawLayout::of(self))?,
+            };
+            writer.write_all(&contents)?;
+            writer.flush()?;
+            Ok(())
+        }
+
+        /// Encodes an image as Netpbm file. The pixels are encoded according to the magic number
+        /// of the image, i.e. as ASCII text for P1, P2 and P3 and as raw bytes for P4, P5, P6 and
+        /// P7.
+        ///
+        /// # Returns:
+        ///  `Result<Vec<u8>, ImageError>` - The contents of the file, or the reason why it could
+        ///  not be encoded
+        fn encode_netpbm(&self) -> Result<Vec<u8>, ImageError> {
+            let channels = self.channels();
+            let body = match self.magic_number.as_str() {
+                "P1" => self.ascii_bits(),
+                "P2" | "P3" => self.ascii_pixels(channels),
+                "P4" => self.binary_bits(),
+                "P5" | "P6" | "P7" => self.binary_pixels(channels, self.scale),
+                _ => {
+                    return Err(ImageError::Unsupported(format!(
+                        "Unsupported magic number: {}",
+                        self.magic_number
+                    )))
+                }
+            };
+            let mut file = Vec::new();
+            writeln!(file, "{}", self.magic_number)?;
+            for comment in &self.comments {
+                writeln!(file, "# {comment}")?;
+            }
+            if let Some(tuple_type) = &self.tuple_type {
+                writeln!(file, "WIDTH {}", self.pixels.ncols())?;
+                writeln!(file, "HEIGHT {}", self.pixels.nrows())?;
+                writeln!(file, "DEPTH {}", tuple_type.depth)?;
+                writeln!(file, "MAXVAL {}", self.scale)?;
+                writeln!(file, "TUPLTYPE {}", tuple_type.name)?;
+                writeln!(file, "ENDHDR")?;
+            } else {
+                writeln!(file, "{} {}", self.pixels.ncols(), self.pixels.nrows())?;
+                if !Self::is_bitmap(&self.magic_number) {
+                    writeln!(file, "{}", self.scale)?;
+                }
+            }
+            file.extend_from_slice(&body);
+            Ok(file)
+        }
+
+        /// Encodes the pixels as ASCII text, one image row per line. The samples are scaled to
+        /// the scale of the image.
+        ///
+        /// # Parameters:
+        ///  `channels` - The number of values per pixel, 1 for gray and 3 for rgb
+        fn ascii_pixels(&self, channels: usize) -> Vec<u8> {
             let mut buffer = String::new();
             for y in 0..self.pixels.nrows() {
                 for x in 0..self.pixels.ncols() {
-                    let pixel = &self.pixels[(y, x)];
-                    let red = pixel.red;
-                    let green = pixel.green;
-                    let blue = pixel.blue;
-                    write!(buffer, "{red:3} {green:3} {blue:3} ").expect("Could not write pixel");
+                    let pixel = self.pixels[(y, x)].denormalize(self.scale);
+                    if channels == 1 {
+                        let gray = pixel.gray();
+                        write!(buffer, "{gray:3} ").expect("Could not write pixel");
+                    } else {
+                        let red = pixel.red;
+                        let green = pixel.green;
+                        let blue = pixel.blue;
+                        write!(buffer, "{red:3} {green:3} {blue:3} ")
+                            .expect("Could not write pixel");
+                    }
                 }
                 writeln!(buffer).expect("Could not write newline");
             }
-            file.write_all(buffer.as_bytes())
-                .expect("Could not write buffer to file");
-            buffer.clear();
+            buffer.into_bytes()
+        }
+
+        /// Encodes the pixels as raw bytes, in row-major order. The samples are scaled to `scale`,
+        /// and written as two bytes in big-endian order if the scale exceeds 255.
+        ///
+        /// # Parameters:
+        ///  `channels` - The number of samples per pixel, 1 for gray, 2 for gray and alpha, 3 for
+        ///  rgb and 4 for rgb and alpha
+        ///  `scale` - The maximum value of each sample
+        #[allow(clippy::cast_possible_truncation)]
+        pub fn binary_pixels(&self, channels: usize, scale: u16) -> Vec<u8> {
+            let sample_bytes = Self::sample_bytes(scale);
+            let mut buffer = Vec::with_capacity(self.pixels.len() * channels * sample_bytes);
+            let mut push = |sample: u16| {
+                if sample_bytes == 2 {
+                    buffer.extend_from_slice(&sample.to_be_bytes());
+                } else {
+                    buffer.push(sample as u8);
+                }
+            };
+            for y in 0..self.pixels.nrows() {
+                for x in 0..self.pixels.ncols() {
+                    let pixel = self.pixels[(y, x)].denormalize(scale);
+                    if channels <= 2 {
+                        push(pixel.gray());
+                    } else {
+                        push(pixel.red);
+                        push(pixel.green);
+                        push(pixel.blue);
+                    }
+                    if channels == 2 || channels == 4 {
+                        push(pixel.alpha);
+                    }
+                }
+            }
+            buffer
+        }
+
+        /// Encodes the pixels as ASCII bits, one image row per line.
+        fn ascii_bits(&self) -> Vec<u8> {
+            let mut buffer = String::new();
+            for y in 0..self.pixels.nrows() {
+                for x in 0..self.pixels.ncols() {
+                    let bit = u8::from(self.pixels[(y, x)].bit());
+                    write!(buffer, "{bit} ").expect("Could not write pixel");
+                }
+                writeln!(buffer).expect("Could not write newline");
+            }
+            buffer.into_bytes()
+        }
+
+        /// Encodes the pixels as packed bits, with each row padded to a full byte.
+        fn binary_bits(&self) -> Vec<u8> {
+            let row_bytes = self.pixels.ncols().div_ceil(8);
+            let mut buffer = vec![0; row_bytes * self.pixels.nrows()];
+            for y in 0..self.pixels.nrows() {
+                for x in 0..self.pixels.ncols() {
+                    if self.pixels[(y, x)].bit() {
+                        buffer[y * row_bytes + x / 8] |= 0x80 >> (x % 8);
+                    }
+                }
+            }
+            buffer
+        }
+
+        /// Sets the encoding used when writing the image, while keeping the image type, e.g. a
+        /// Portable Graymap is written as P2 (ASCII) or P5 (binary). Portable Arbitrary Maps
+        /// only exist in binary, so they keep their encoding.
+        ///
+        /// # Parameters:
+        ///  `encoding` - ASCII or binary encoding
+        pub fn set_encoding(&mut self, encoding: Encoding) {
+            self.magic_number = match (self.magic_number.as_str(), encoding) {
+                ("P7", _) => "P7".to_string(),
+                ("P1" | "P4", Encoding::Ascii) => "P1".to_string(),
+                ("P1" | "P4", Encoding::Binary) => "P4".to_string(),
+                ("P2" | "P5", Encoding::Ascii) => "P2".to_string(),
+                ("P2" | "P5", Encoding::Binary) => "P5".to_string(),
+                (_, Encoding::Ascii) => "P3".to_string(),
+                (_, Encoding::Binary) => "P6".to_string(),
+            };
+        }
+
+        /// Sets the kind of Netpbm image that belongs to the extension of a file name, while
+        /// keeping the encoding, e.g. a Portable Graymap for `.pgm`. A Portable Arbitrary Map
+        /// (`.pam`) keeps the samples of the image. Other extensions, like `.pnm`, keep the kind.
+        ///
+        /// # Parameters:
+        ///  `filename` - the name of the file the image is written to
+        pub fn set_netpbm_kind(&mut self, filename: &str) {
+            let extension = Path::new(filename)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(str::to_ascii_lowercase);
+            let (ascii, binary) = match extension.as_deref() {
+                Some("pbm") => ("P1", "P4"),
+                Some("pgm") => ("P2", "P5"),
+                Some("ppm") => ("P3", "P6"),
+                Some("pam") => {
+                    if self.tuple_type.is_none() {
+                        self.tuple_type = Some(TupleType::with_depth(self.channels()));
+                    }
+                    self.magic_number = "P7".to_string();
+                    return;
+                }
+                _ => return,
+            };
+            let is_ascii = matches!(self.magic_number.as_str(), "P1" | "P2" | "P3");
+            self.magic_number = if is_ascii { ascii } else { binary }.to_string();
+            self.tuple_type = None;
+        }
+
+        /// Sets the scale used when writing the image. As the samples are normalized, this
+        /// converts the image to the new scale.
+        ///
+        /// # Parameters:
+        ///  `scale` - the maximum value of each color, between 1 and 65535
+        pub fn set_scale(&mut self, scale: u16) -> Result<(), ImageError> {
+            if scale == 0 {
+                return Err(ImageError::Unsupported(
+                    "The scale must be positive".to_string(),
+                ));
+            }
+            if !Self::is_bitmap(&self.magic_number) {
+                self.scale = scale;
+            }
+            Ok(())
         }
 
         //=== IMAGE STATISTICS ====================================================================
 
         /// Returns the brightness of the pixels, defined as the sum of the color channels, divided
-        /// by three.
+        /// by three, relative to the scale of the image.
         ///
         /// # Returns:
-        ///  `u32`-  Brightness of the image
-        fn brightness(&self) -> u32 {
-            let size: u32 = (self.pixels.nrows() * self.pixels.ncols())
+        ///  `u16`-  Brightness of the image
+        fn brightness(&self) -> u16 {
+            let size: u64 = (self.pixels.nrows() * self.pixels.ncols())
                 .try_into()
                 .unwrap();
-            let mut sum: u32 = 0;
+            let mut sum: u64 = 0;
             for pixel in &self.pixels {
-                sum += (u32::from(pixel.red) + u32::from(pixel.green) + u32::from(pixel.blue)) / 3;
+                sum += u64::from(pixel.gray());
             }
-            sum / size
+            let brightness = u16::try_from(sum / size).unwrap();
+            pixel::rescale(brightness, u16::MAX, self.scale)
         }
 
         /// Print statistics from the image.
@@ -169,12 +835,62 @@ pub mod image {
             println!("Type:       {}", self.magic_number);
             println!("Height:     {}", self.pixels.nrows());
             println!("Width:      {}", self.pixels.ncols());
+            println!("Scale:      {}", self.scale);
             println!("Brightness: {}", self.brightness());
         }
+    }
+
+    impl<P: ImagePixel> Image<P> {
+        //=== PIXELS ==============================================================================
+
+        /// Returns the number of samples stored per pixel in the file: one for Portable Bitmaps,
+        /// Graymaps and grayscale FloatMaps, three for Portable Pixmaps and color FloatMaps, and
+        /// the depth of the tuple type for Portable Arbitrary Maps, or all samples of the pixels
+        /// without one.
+        pub fn channels(&self) -> usize {
+            match self.magic_number.as_str() {
+                "P3" | "P6" | "PF" => 3,
+                "P7" => self
+                    .tuple_type
+                    .as_ref()
+                    .map_or(P::CHANNELS, |tuple_type| tuple_type.depth),
+                _ => 1,
+            }
+        }
+
+        /// Creates an image with the same type and scale, but different pixels.
+        ///
+        /// # Parameters:
+        ///  `pixels` - the pixel matrix of the new image
+        fn with_pixels(&self, pixels: DMatrix<P>) -> Image<P> {
+            Image {
+                format: self.format,
+                magic_number: self.magic_number.clone(),
+                scale: self.scale,
+                comments: self.comments.clone(),
+                tuple_type: self.tuple_type.clone(),
+                pixels,
+            }
+        }
+
+        /// Converts an image to 16-bit pixels, e.g. for previews and animations.
+        ///
+        /// # Returns:
+        ///  `Image` - The image with the same type and scale, and converted pixels
+        pub fn to_pixels(&self) -> Image {
+            Image {
+                format: self.format,
+                magic_number: self.magic_number.clone(),
+                scale: self.scale,
+                comments: self.comments.clone(),
+                tuple_type: self.tuple_type.clone(),
+                pixels: self.pixels.map(P::to_pixel),
+            }
+        }
 
         //=== SEAM CARVING ========================================================================
 
-        /// Seam carves an image using the following procedure:
+        /// Seam carves an image in place using the following procedure:
         ///     * Calculate the appropriate energy matrix.
         ///     * Find the pixel with the minimal energy at the width/height up to which the energy
         ///     is calculated to.
@@ -183,68 +899,101 @@ pub mod image {
         ///
         /// # Parameters
         ///  `iterations` - how many seams should be removed
-        ///  `output` - where the output image should be stored
         ///  `vertical` - whether vertical or horizontal seams should be removed
-        pub fn seam_carve(&mut self, iterations: usize, output: &String, vertical: bool) {
+        ///  `animation` - an animation that records each step, with the removed seam highlighted
+        pub fn seam_carve(
+            &mut self,
+            iterations: usize,
+            vertical: bool,
+            mut animation: Option<&mut Animation>,
+        ) -> Result<(), ImageError> {
+            let (size, side) = if vertical {
+                (self.pixels.ncols(), "columns")
+            } else {
+                (self.pixels.nrows(), "rows")
+            };
+            if iterations >= size {
+                return Err(ImageError::Dimensions(format!(
+                    "Cannot carve {iterations} seams out of {size} {side}"
+                )));
+            }
             if vertical {
                 let width = self.pixels.ncols();
                 let mut border = self.pixels.ncols();
-                let mut energy_matrix: DMatrix<u32> =
+                let mut energy_matrix: DMatrix<u64> =
                     DMatrix::from_element(self.pixels.nrows(), self.pixels.ncols(), 0);
                 for _ in 0..iterations {
-                    energy::calculate_vertical_energy_matrix(self, &mut energy_matrix, width);
+                    energy::calculate_vertical_energy_matrix(
+                        &self.pixels,
+                        &mut energy_matrix,
+                        width,
+                    );
                     let x = energy::calculate_min_energy_column(&energy_matrix, border);
                     let seam = energy::calculate_optimal_vertical_path(&energy_matrix, border, x);
-                    self.carve_vertical_path(border, &seam);
+                    if let Some(animation) = animation.as_deref_mut() {
+                        self.record_seam(animation, border, &seam, true)?;
+                    }
+                    energy::carve_vertical_path(&mut self.pixels, border, &seam);
                     border -= 1;
                 }
-                self.crop(output, 0, width - iterations, 0, self.pixels.nrows());
+                if let Some(animation) = animation {
+                    self.record_seam(animation, border, &[], true)?;
+                }
+                *self = self.crop(0, width - iterations, 0, self.pixels.nrows())?;
             } else {
                 let height = self.pixels.nrows();
                 let mut border = self.pixels.nrows();
-                let mut energy_matrix: DMatrix<u32> =
+                let mut energy_matrix: DMatrix<u64> =
                     DMatrix::from_element(self.pixels.nrows(), self.pixels.ncols(), 0);
                 for _ in 0..iterations {
-                    energy::calculate_horizontal_energy_matrix(self, &mut energy_matrix, height);
+                    energy::calculate_horizontal_energy_matrix(
+                        &self.pixels,
+                        &mut energy_matrix,
+                        height,
+                    );
                     let x = energy::calculate_min_energy_row(&energy_matrix, border);
                     let seam = energy::calculate_optimal_horizontal_path(&energy_matrix, border, x);
-                    self.carve_horizontal_path(border, &seam);
+                    if let Some(animation) = animation.as_deref_mut() {
+                        self.record_seam(animation, border, &seam, false)?;
+                    }
+                    energy::carve_horizontal_path(&mut self.pixels, border, &seam);
                     border -= 1;
                 }
-                self.crop(output, 0, self.pixels.ncols(), 0, height - iterations);
-            }
-        }
-
-        /// Carves a vertical path.
-        ///
-        /// # Parameters
-        ///  `border` - the width up to which the energy matrix is calculated to
-        ///  `seam` - the seam to carve
-        fn carve_vertical_path(&mut self, border: usize, seam: &[usize]) {
-            for j in 0..self.pixels.nrows() {
-                let col = *seam.get(j).unwrap();
-                for i in col..border - 1 {
-                    self.pixels[(j, i)].red = self.pixels[(j, i + 1)].red;
-                    self.pixels[(j, i)].green = self.pixels[(j, i + 1)].green;
-                    self.pixels[(j, i)].blue = self.pixels[(j, i + 1)].blue;
+                if let Some(animation) = animation {
+                    self.record_seam(animation, border, &[], false)?;
                 }
+                *self = self.crop(0, self.pixels.ncols(), 0, height - iterations)?;
             }
+            Ok(())
         }
 
-        /// Carves a horizontal path.
+        /// Adds the part of the image that is not carved yet to an animation, with a seam
+        /// highlighted in red.
         ///
         /// # Parameters
-        ///  `border` - the height up to which the energy matrix is calculated to
-        ///  `seam` - the seam to carve
-        fn carve_horizontal_path(&mut self, border: usize, seam: &[usize]) {
-            for j in 0..self.pixels.ncols() {
-                let row = *seam.get(j).unwrap();
-                for i in row..border - 1 {
-                    self.pixels[(i, j)].red = self.pixels[(i + 1, j)].red;
-                    self.pixels[(i, j)].green = self.pixels[(i + 1, j)].green;
-                    self.pixels[(i, j)].blue = self.pixels[(i + 1, j)].blue;
-                }
+        ///  `animation` - the animation to add the frame to
+        ///  `border` - the width or height up to which the image is not carved yet
+        ///  `seam` - the seam to highlight, which may be empty
+        ///  `vertical` - whether the seam is vertical or horizontal
+        fn record_seam(
+            &self,
+            animation: &mut Animation,
+            border: usize,
+            seam: &[usize],
+            vertical: bool,
+        ) -> Result<(), ImageError> {
+            let size = if vertical {
+                (self.pixels.nrows(), border)
+            } else {
+                (border, self.pixels.ncols())
+            };
+            let mut pixels = self.pixels.view((0, 0), size).into_owned();
+            for (j, &i) in seam.iter().enumerate() {
+                let position = if vertical { (j, i) } else { (i, j) };
+                pixels[position] = P::from_pixel(Pixel::from_rgb(u16::MAX, 0, 0));
             }
+            animation.add_frame(&self.with_pixels(pixels).to_pixels())?;
+            Ok(())
         }
 
         //=== IMAGE MANIPULATION ==================================================================
@@ -252,159 +1001,100 @@ pub mod image {
         /// Crop an image
         ///
         /// # Parameters:
-        ///  `filename` - path to the file (as String)
         ///  `x1` - lower vertical border
         ///  `x2` - upper vertical border
         ///  `y1` - left horizontal border
         ///  `y2` - right horizontal border
-        pub fn crop(&self, filename: &String, x1: usize, x2: usize, y1: usize, y2: usize) {
-            assert!(x1 <= self.pixels.ncols());
-            assert!(x2 <= self.pixels.ncols());
-            assert!(y1 <= self.pixels.nrows());
-            assert!(y2 <= self.pixels.nrows());
-            let mut file = fs::File::create(filename).expect("Could not write to file");
-            writeln!(file, "{}", self.magic_number).expect("Could not write magic number.");
-            writeln!(file, "{} {}", x2 - x1, y2 - y1).expect("Could not write height and width.");
-            writeln!(file, "{}", self.scale).expect("Could not write scale");
-            let mut buffer = String::new();
-            for y in y1..y2 {
-                for x in x1..x2 {
-                    let pixel = &self.pixels[(y, x)];
-                    let red = pixel.red;
-                    let green = pixel.green;
-                    let blue = pixel.blue;
-                    write!(buffer, "{red:3} {green:3} {blue:3} ").expect("Could not write pixel");
-                }
-                writeln!(buffer).expect("Could not write newline");
-            }
-            file.write_all(buffer.as_bytes())
-                .expect("Could not write buffer to file");
-            buffer.clear();
+        ///
+        /// # Returns:
+        ///  `Result<Image<P>, ImageError>` - The cropped image, or the reason why the borders are
+        ///  invalid
+        pub fn crop(
+            &self,
+            x1: usize,
+            x2: usize,
+            y1: usize,
+            y2: usize,
+        ) -> Result<Image<P>, ImageError> {
+            check_crop(self.pixels.shape(), x1, x2, y1, y2)?;
+            let pixels = self.pixels.view((y1, x1), (y2 - y1, x2 - x1)).into_owned();
+            Ok(self.with_pixels(pixels))
         }
 
         /// Transposes an image.
         ///
-        /// Parameters:
-        ///  `filename` - Path to the file
-        pub fn transpose(&self, filename: &String) {
-            let mut file = fs::File::create(filename).expect("Could not write to file");
-            writeln!(file, "{}", self.magic_number).expect("Could not write magic number.");
-            writeln!(file, "{} {}", self.pixels.nrows(), self.pixels.ncols())
-                .expect("Could not write height and width.");
-            writeln!(file, "{}", self.scale).expect("Could not write scale");
-            let mut buffer = String::new();
-            for x in 0..self.pixels.ncols() {
-                for y in 0..self.pixels.nrows() {
-                    let pixel = &self.pixels[(y, x)];
-                    let red = pixel.red;
-                    let green = pixel.green;
-                    let blue = pixel.blue;
-                    write!(buffer, "{red:3} {green:3} {blue:3} ").expect("Could not write pixel");
-                }
-                writeln!(buffer).expect("Could not write newline");
-            }
-            file.write_all(buffer.as_bytes())
-                .expect("Could not write buffer to file");
-            buffer.clear();
+        /// # Returns:
+        ///  `Image<P>` - The transposed image
+        pub fn transpose(&self) -> Image<P> {
+            self.with_pixels(self.pixels.transpose())
         }
 
         /// Rotates an image.
         ///
-        /// Parameters:
-        ///  `filename` - Path to the file
-        pub fn rotate(&self, filename: &String) {
-            let mut file = fs::File::create(filename).expect("Could not write to file");
-            writeln!(file, "{}", self.magic_number).expect("Could not write magic number.");
-            writeln!(file, "{} {}", self.pixels.nrows(), self.pixels.ncols())
-                .expect("Could not write height and width.");
-            writeln!(file, "{}", self.scale).expect("Could not write scale");
-            let mut buffer = String::new();
-            for x in 0..self.pixels.ncols() {
-                for y in 0..self.pixels.nrows() {
-                    let pixel = &self.pixels[(self.pixels.nrows() - 1 - y, x)];
-                    let red = pixel.red;
-                    let green = pixel.green;
-                    let blue = pixel.blue;
-                    write!(buffer, "{red:3} {green:3} {blue:3} ").expect("Could not write pixel");
-                }
-                writeln!(buffer).expect("Could not write newline");
-            }
-            file.write_all(buffer.as_bytes())
-                .expect("Could not write buffer to file");
-            buffer.clear();
+        /// # Returns:
+        ///  `Image<P>` - The rotated image
+        pub fn rotate(&self) -> Image<P> {
+            let rows = self.pixels.nrows();
+            let pixels = DMatrix::from_fn(self.pixels.ncols(), rows, |x, y| {
+                self.pixels[(rows - 1 - y, x)]
+            });
+            self.with_pixels(pixels)
         }
 
-        /// Rotate an image.
-        ///
-        /// # Parameters:
-        ///  `filename` - Path to the output file
-        pub fn invert(&mut self, filename: &String) {
-            let mut file = fs::File::create(filename).expect("Could not write to file");
-            writeln!(file, "{}", self.magic_number).expect("Could not write magic number");
-            writeln!(file, "{} {}", self.pixels.ncols(), self.pixels.nrows())
-                .expect("Could not write height and width.");
-            writeln!(file, "{}", self.scale).expect("Could not write scale");
-            let mut buffer = String::new();
-            for y in 0..self.pixels.nrows() {
-                for x in 0..self.pixels.ncols() {
-                    let pixel = &mut self.pixels[(y, x)];
-                    pixel.invert();
-                    let red = pixel.red;
-                    let green = pixel.green;
-                    let blue = pixel.blue;
-                    write!(buffer, "{red:3} {green:3} {blue:3} ").expect("Could not write pixel");
-                }
-                writeln!(buffer).expect("Could not write newline");
+        /// Inverts an image in place.
+        pub fn invert(&mut self) {
+            for pixel in self.pixels.iter_mut() {
+                pixel.invert();
             }
-            file.write_all(buffer.as_bytes())
-                .expect("Could not write buffer to file");
-            buffer.clear();
         }
 
         /// Mirror an image
         ///
-        /// # Parameters:
-        ///  `filename` - path to the file (as String)
-        pub fn mirror(&self, filename: &String) {
-            let mut file = fs::File::create(filename).expect("Could not write to file");
-            writeln!(file, "{}", self.magic_number).expect("Could not write magic number.");
-            writeln!(file, "{} {}", self.pixels.ncols(), self.pixels.nrows())
-                .expect("Could not write height and width.");
-            writeln!(file, "{}", self.scale).expect("Could not write scale");
-            let mut buffer = String::new();
-            for y in 0..self.pixels.nrows() {
-                for x in 0..self.pixels.ncols() {
-                    let pixel = &self.pixels[(y, self.pixels.ncols() - 1 - x)];
-                    let red = pixel.red;
-                    let green = pixel.green;
-                    let blue = pixel.blue;
-                    write!(buffer, "{red:3} {green:3} {blue:3} ").expect("Could not write pixel");
-                }
-                writeln!(buffer).expect("Could not write newline");
-            }
-            file.write_all(buffer.as_bytes())
-                .expect("Could not write buffer to file");
-            buffer.clear();
+        /// # Returns:
+        ///  `Image<P>` - The mirrored image
+        pub fn mirror(&self) -> Image<P> {
+            let cols = self.pixels.ncols();
+            let pixels = DMatrix::from_fn(self.pixels.nrows(), cols, |y, x| {
+                self.pixels[(y, cols - 1 - x)]
+            });
+            self.with_pixels(pixels)
         }
 
-        /// Landfill using a color and a point
+        //=== LANDFILL ============================================================================
+
+        /// Landfill in place using a color and a point. The filled pixels keep their opacity.
         ///
         /// # Parameters:
-        ///  `filename` - path to the file (as String)
-        ///  `coords` - x and y coordinaates
-        ///  `rgb` - red, green and blue pixel values
-        pub fn landfill(&mut self, filename: &String, coords: (usize, usize), rgb: (u8, u8, u8)) {
-            env_logger::init();
-            let (y, x) = coords;
+        ///  `coords` - the column x and the row y of the point
+        ///  `rgb` - red, green and blue pixel values, relative to the scale of the image
+        pub fn landfill(
+            &mut self,
+            coords: (usize, usize),
+            rgb: (u16, u16, u16),
+        ) -> Result<(), ImageError> {
+            let (x, y) = coords;
             let (red, green, blue) = rgb;
-            if x >= self.pixels.ncols() && y >= self.pixels.nrows() {
-                return;
-            }
-            let original_point = (
-                self.pixels[(y, x)].red,
-                self.pixels[(y, x)].green,
-                self.pixels[(y, x)].blue,
+            let fill = P::from_pixel(
+                Pixel {
+                    red,
+                    green,
+                    blue,
+                    alpha: self.scale,
+                }
+                .normalize(self.scale),
             );
+            if x >= self.pixels.ncols() || y >= self.pixels.nrows() {
+                return Err(ImageError::OutOfBounds {
+                    x,
+                    y,
+                    width: self.pixels.ncols(),
+                    height: self.pixels.nrows(),
+                });
+            }
+            let original_point = self.pixels[(y, x)];
+            if Self::inside(fill, original_point) {
+                return Ok(());
+            }
             let mut stack: Vec<(usize, usize)> = vec![];
             stack.push((y, x));
             while !stack.is_empty() {
@@ -413,9 +1103,7 @@ pub mod image {
                 };
                 let mut px = self.pixels[(y1, x1)];
                 if Self::inside(original_point, px) {
-                    self.pixels[(y1, x1)].red = red;
-                    self.pixels[(y1, x1)].green = green;
-                    self.pixels[(y1, x1)].blue = blue;
+                    self.pixels[(y1, x1)] = px.with_colors(fill);
                 }
                 if x1 + 1 < self.pixels.ncols() && y1 < self.pixels.nrows() {
                     px = self.pixels[(y1, x1 + 1)];
@@ -423,10 +1111,10 @@ pub mod image {
                         stack.push((y1, x1 + 1));
                     }
                 }
-                if x1 - 1 < self.pixels.ncols() && y1 < self.pixels.nrows() {
-                    px = self.pixels[(y1, x1 - 1)];
+                if let Some(x0) = x1.checked_sub(1) {
+                    px = self.pixels[(y1, x0)];
                     if Self::inside(original_point, px) {
-                        stack.push((y1, x1 - 1));
+                        stack.push((y1, x0));
                     }
                 }
                 if x1 < self.pixels.ncols() && y1 + 1 < self.pixels.nrows() {
@@ -435,26 +1123,232 @@ pub mod image {
                         stack.push((y1 + 1, x1));
                     }
                 }
-                if x1 < self.pixels.ncols() && y1 - 1 < self.pixels.nrows() {
-                    px = self.pixels[(y1 - 1, x1)];
+                if let Some(y0) = y1.checked_sub(1) {
+                    px = self.pixels[(y0, x1)];
                     if Self::inside(original_point, px) {
-                        stack.push((y1 - 1, x1));
+                        stack.push((y0, x1));
                     }
                 }
             }
-            self.write(filename);
+            Ok(())
         }
 
         /// Checks whether the pixel has the required colors.
         ///
         /// # Parameters
-        ///  `rgb` - red, green and blue pixel values
+        ///  `colors` - a pixel with the required colors
         ///  `pixel` - the pixel to inspect
         ///
         /// # Returns
         ///  true if the pixel has the required colors
-        fn inside(rgb: (u8, u8, u8), pixel: Pixel) -> bool {
-            rgb.0 == pixel.red && rgb.1 == pixel.green && rgb.2 == pixel.blue
+        fn inside(colors: P, pixel: P) -> bool {
+            pixel.with_colors(colors) == pixel
+        }
+    }
+
+    /// Checks that the borders of a crop lie within an image and enclose at least one pixel.
+    ///
+    /// # Parameters:
+    ///  `shape` - the height and width of the image
+    ///  `x1` - lower vertical border
+    ///  `x2` - upper vertical border
+    ///  `y1` - left horizontal border
+    ///  `y2` - right horizontal border
+    pub fn check_crop(
+        (height, width): (usize, usize),
+        x1: usize,
+        x2: usize,
+        y1: usize,
+        y2: usize,
+    ) -> Result<(), ImageError> {
+        for (x, y) in [(x1, y1), (x2, y2)] {
+            if x > width || y > height {
+                return Err(ImageError::OutOfBounds {
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+        }
+        if x1 >= x2 || y1 >= y2 {
+            return Err(ImageError::Dimensions(format!(
+                "The crop from ({x1}, {y1}) to ({x2}, {y2}) is empty"
+            )));
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Returns an image of 3 by 2 pixels, whose colors are derived from 8-bit samples. They
+        /// are gray for one or two channels, and opaque for one or three channels.
+        fn image(magic_number: &str, scale: u16, channels: usize, samples: [u16; 6]) -> Image {
+            let pixels = DMatrix::from_row_slice(2, 3, &samples).map(|sample| {
+                let (gray, transparent) = (Pixel::from_gray(sample), 255 - sample);
+                match channels {
+                    1 => Pixel { alpha: 255, ..gray },
+                    2 => Pixel {
+                        alpha: transparent,
+                        ..gray
+                    },
+                    _ => Pixel {
+                        red: sample,
+                        green: 255 - sample,
+                        blue: sample / 2,
+                        alpha: if channels == 4 { transparent } else { 255 },
+                    },
+                }
+                .normalize(255)
+            });
+            Image {
+                format: Format::Netpbm,
+                magic_number: magic_number.to_string(),
+                scale,
+                comments: vec!["first".to_string(), "second".to_string()],
+                tuple_type: (magic_number == "P7").then(|| TupleType {
+                    name: "CUSTOM".to_string(),
+                    depth: channels,
+                }),
+                pixels,
+            }
+        }
+
+        /// Writes and reads an image, and checks that nothing changed.
+        fn assert_round_trip(image: &Image) {
+            let mut contents = Vec::new();
+            image.write_to(&mut contents).unwrap();
+            let (decoded, length) = Image::decode_netpbm(&contents).unwrap();
+            assert!(contents[length..].iter().all(u8::is_ascii_whitespace));
+            assert_eq!(decoded.magic_number, image.magic_number);
+            assert_eq!(decoded.scale, image.scale);
+            assert_eq!(decoded.comments, image.comments);
+            assert_eq!(decoded.tuple_type, image.tuple_type);
+            assert_eq!(decoded.pixels, image.pixels);
+        }
+
+        #[test]
+        fn netpbm_round_trips() {
+            let bits = [0, 255, 255, 0, 0, 255];
+            let samples = [0, 1, 17, 128, 254, 255];
+            assert_round_trip(&image("P1", 1, 1, bits));
+            assert_round_trip(&image("P4", 1, 1, bits));
+            for scale in [255, u16::MAX] {
+                assert_round_trip(&image("P2", scale, 1, samples));
+                assert_round_trip(&image("P3", scale, 3, samples));
+                assert_round_trip(&image("P5", scale, 1, samples));
+                assert_round_trip(&image("P6", scale, 3, samples));
+                for depth in 1..=4 {
+                    assert_round_trip(&image("P7", scale, depth, samples));
+                }
+            }
+        }
+
+        #[test]
+        fn reads_concatenated_images() {
+            let mut contents = Vec::new();
+            image("P6", 255, 3, [1; 6]).write_to(&mut contents).unwrap();
+            image("P1", 1, 1, [0; 6]).write_to(&mut contents).unwrap();
+            let (first, length) = Image::decode_netpbm(&contents).unwrap();
+            assert_eq!(first.magic_number, "P6");
+            let (second, _) = Image::decode_netpbm(&contents[length..]).unwrap();
+            assert_eq!(second.magic_number, "P1");
+        }
+
+        #[test]
+        fn reads_samples_against_the_scale() {
+            let (image, _) = Image::decode_netpbm(b"P2 2 1 15 0 15").unwrap();
+            assert_eq!(image.pixels[(0, 0)], Pixel::from_gray(0));
+            assert_eq!(image.pixels[(0, 1)], Pixel::from_gray(u16::MAX));
+            let (image, _) = Image::decode_netpbm(b"P1 3 1 101").unwrap();
+            assert_eq!(image.pixels[(0, 1)], Pixel::from_gray(u16::MAX));
+        }
+
+        #[test]
+        fn rejects_invalid_and_oversized_images() {
+            for contents in [
+                &b"P6\n2 2\n255\n\0\0\0"[..],
+                b"P3\n1 1\n255\n1 2",
+                b"P2\n1 1\n15\n16\n",
+                b"P1\n2 1\n02",
+                b"P6\n4294967295 4294967295\n65535\n",
+                b"P5\n4294967295 4294967295\n255\n\0",
+                b"P4\n4294967295 4294967295\n",
+                b"P1\n4294967295 4294967295\n0 1",
+                b"P3\n4294967295 4294967295\n255\n1 2 3",
+                b"P7\nWIDTH 4294967295\nHEIGHT 4294967295\nDEPTH 4\nMAXVAL 65535\nENDHDR\n",
+            ] {
+                let result = Image::decode_netpbm(contents);
+                assert!(result.is_err(), "{}", String::from_utf8_lossy(contents));
+            }
+        }
+
+        #[test]
+        fn takes_the_kind_of_netpbm_image_from_the_extension() {
+            let mut image = image("P7", 255, 4, [0; 6]);
+            for (filename, magic_number) in [
+                ("out.ppm", "P6"),
+                ("out.PGM", "P5"),
+                ("out.pnm", "P5"),
+                ("out.png", "P5"),
+                ("out.pbm", "P4"),
+                ("out.pam", "P7"),
+            ] {
+                image.set_netpbm_kind(filename);
+                assert_eq!(image.magic_number, magic_number, "{filename}");
+                assert_eq!(image.tuple_type.is_some(), magic_number == "P7");
+            }
+            assert_eq!(image.tuple_type, Some(TupleType::with_depth(1)));
+            image.set_netpbm_kind("out.pgm");
+            image.set_encoding(Encoding::Ascii);
+            image.set_netpbm_kind("out.ppm");
+            assert_eq!(image.magic_number, "P3");
+        }
+
+        #[test]
+        fn landfills_from_the_borders() {
+            let mut image = image("P5", 255, 1, [0, 0, 255, 0, 255, 255]);
+            let original = image.pixels.clone();
+            image.landfill((1, 0), (0, 0, 0)).unwrap();
+            assert_eq!(image.pixels, original);
+            image.landfill((1, 0), (255, 255, 255)).unwrap();
+            assert!(image.pixels.iter().all(|pixel| pixel.gray() == u16::MAX));
+        }
+
+        #[test]
+        fn landfills_at_the_column_and_row() {
+            let mut image = image("P5", 255, 1, [0, 255, 255, 0, 0, 255]);
+            image.landfill((2, 0), (0, 0, 0)).unwrap();
+            let grays: Vec<u16> = image.pixels.iter().map(Pixel::gray).collect();
+            assert_eq!(grays, [0; 6]);
+            let error = image.landfill((0, 2), (0, 0, 0)).unwrap_err();
+            assert!(matches!(
+                error,
+                ImageError::OutOfBounds {
+                    x: 0,
+                    y: 2,
+                    width: 3,
+                    height: 2
+                }
+            ));
+        }
+
+        #[test]
+        fn rejects_empty_crops_and_carves() {
+            let image = image("P5", 255, 1, [0, 0, 255, 0, 255, 255]);
+            assert!(image.crop(0, 3, 0, 2).is_ok());
+            assert!(image.crop(1, 1, 0, 2).is_err());
+            assert!(image.crop(0, 3, 2, 2).is_err());
+            for (iterations, vertical, shape) in [(2, true, (2, 1)), (1, false, (1, 3))] {
+                let mut carved = image.crop(0, 3, 0, 2).unwrap();
+                carved.seam_carve(iterations, vertical, None).unwrap();
+                assert_eq!(carved.pixels.shape(), shape);
+                assert!(carved
+                    .seam_carve(shape.0.max(shape.1), vertical, None)
+                    .is_err());
+            }
         }
     }
 }
@@ -0,0 +1,66 @@
+//! This piece of software contains some basic functionality to manipulate images. It is meant as
+//! to study Rust for me. The command line interface `simp` is built on top of this library, and
+//! only compiled with the `cli` feature, so that the library does not depend on clap.
+
+#![warn(missing_docs)]
+
+/// This crate contains the conversion between images and the BMP format.
+pub mod bmp_utils;
+
+/// Seam Carving uses color differences of neighboring pixels as dispensability score. This
+/// difference is called energy. This crate contains methods to calculate the energy of an image
+/// and to find the optimal path according to this dispensability score.
+pub mod energy_utils;
+
+/// This crate contains the errors of reading, writing and manipulating images.
+pub mod error_utils;
+
+/// This crate contains the conversion between images and the farbfeld format.
+pub mod farbfeld_utils;
+
+/// This crate contains the data structure that represents high dynamic range images.
+pub mod float_image_utils;
+
+/// This crate contains the reading and writing of streams with several images.
+pub mod frame_utils;
+
+/// This crate contains the recording of animated GIF files.
+pub mod gif_utils;
+
+/// This crate contains a tokenizing parser for the headers of Netpbm images.
+pub mod header_utils;
+
+/// This crate contains the data structure that represents images as pixel matrices and
+/// functionalities as cropping, transposing, inverting, rotating, mirroring, and the more advanced
+/// functionality of seam carving.
+pub mod image_utils;
+
+/// This crate contains the conversion between floating point images and the PFM format.
+pub mod pfm_utils;
+
+/// This crate contains the pixels of images and their color differences.
+pub mod pixel_utils;
+
+/// This crate contains the conversion between images and the PNG format.
+pub mod png_utils;
+
+/// This crate contains the conversion between images and the QOI format.
+pub mod qoi_utils;
+
+/// This crate contains the conversion between images and raw pixel data without header.
+pub mod raw_utils;
+
+/// This crate contains the conversion of images to sixel graphics.
+pub mod sixel_utils;
+
+/// This crate contains the preview of images in a terminal.
+pub mod terminal_utils;
+
+pub use energy_utils::energy;
+pub use error_utils::error::ImageError;
+pub use float_image_utils::float_image::{FloatImage, ToneMapping};
+pub use frame_utils::frame::{FrameWriter, Frames};
+pub use gif_utils::gif::Animation;
+pub use image_utils::image::{Encoding, Format, Image};
+pub use pixel_utils::pixel::{ColorDiff, FloatPixel, ImagePixel, Pixel};
+pub use raw_utils::raw::{ChannelOrder, Endianness, RawLayout};
@@ -1,50 +1,148 @@
-//! This piece of software contains some basic functionality to manipulate images. It is meant as
-//! to study Rust for me.
-
-#[warn(missing_docs)]
-
-/// Seam Carving uses color differences of neighboring pixels as dispensability score. This
-/// difference is called energy. This crate contains methods to calculate the energy of an image
-/// and to find the optimal path according to this dispensability score.
-mod energy_utils;
-
-/// This crate contains the data structure that represents images as pixel matrices and
-/// functionalities as cropping, transposing, inverting, rotating, mirroring, and the more advanced
-/// functionality of seam carving.
-mod image_utils;
-mod pixel_utils;
-use image_utils::image::Image;
+//! The command line interface of simp, which reads images, applies a subcommand to each of them,
+//! and writes the results.
+
+/// This crate contains the batch mode, which processes many input files in parallel.
+mod batch_utils;
+
+/// This crate contains the recipe files, which describe a chain of subcommands.
+mod recipe_utils;
+
 use nalgebra::DMatrix;
-use pixel_utils::pixel::Pixel;
+use simp::terminal_utils::terminal;
+use simp::{
+    Animation, ChannelOrder, Encoding, Endianness, FloatImage, Format, FrameWriter, Frames, Image,
+    ImageError, Pixel, RawLayout, ToneMapping,
+};
 
 extern crate rand;
-use clap::{Parser, Subcommand};
+use batch_utils::batch;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use rand::Rng;
+use recipe_utils::recipe;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::{env, iter, process};
 
 #[derive(Parser)]
-#[command(author, version, about, long_about)]
+#[command(
+    author,
+    version,
+    about,
+    long_about,
+    after_help = "Several subcommands can be chained, e.g. `simp -f in.ppm crop ... mirror`. They \
+                  are applied in order, and the result is written once."
+)]
 struct Cli {
-    #[arg(short, long)]
+    /// Input file, or `-` for the standard input
+    #[arg(short, long, default_value = "-")]
     filename: String,
 
-    #[arg(short, long)]
+    /// Output file, or `-` for the standard output. In batch mode, an output directory or a
+    /// naming template like `out/{name}.png`
+    #[arg(short, long, default_value = "-")]
     output: String,
 
+    /// Processes every file in a directory, or every file that matches a glob pattern, instead
+    /// of the input file
+    #[arg(long)]
+    batch: Option<String>,
+
+    /// Number of worker threads in batch mode, defaults to the number of CPU cores
+    #[arg(long)]
+    jobs: Option<usize>,
+
+    /// Encoding of the output file, defaults to the encoding of the input file
+    #[arg(short, long, value_enum)]
+    encoding: Option<Encoding>,
+
+    /// Scale of the output file, defaults to the scale of the input file
+    #[arg(short, long)]
+    scale: Option<u16>,
+
+    /// Format of the input file, defaults to the format detected from its contents
+    #[arg(long, value_enum)]
+    input_format: Option<Format>,
+
+    /// Format of the output file, defaults to the format given by its extension or the format of
+    /// the input file
+    #[arg(long, value_enum)]
+    output_format: Option<Format>,
+
+    /// Width of raw input images, which have no header
+    #[arg(long)]
+    raw_width: Option<usize>,
+
+    /// Height of raw input images, which have no header
+    #[arg(long)]
+    raw_height: Option<usize>,
+
+    /// Order of the samples of raw images, defaults to rgb for input and to the samples of the
+    /// image for output
+    #[arg(long, value_enum)]
+    channel_order: Option<ChannelOrder>,
+
+    /// Bits per sample of raw images, 8 or 16
+    #[arg(long)]
+    bit_depth: Option<u8>,
+
+    /// Byte order of 16-bit samples of raw images, defaults to big-endian
+    #[arg(long, value_enum)]
+    endianness: Option<Endianness>,
+
+    /// Tone mapping of PFM images that are converted to 8 bits per sample
+    #[arg(long, value_enum, default_value_t = ToneMapping::Clamp)]
+    tone_mapping: ToneMapping,
+
+    /// Print a preview of the resulting images to the terminal
+    #[arg(short, long, global = true)]
+    preview: bool,
+
+    /// Print previews as sixel graphics, instead of colored half blocks
+    #[arg(long, global = true)]
+    sixel: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
-#[derive(Subcommand)]
+#[derive(Clone, Subcommand, Deserialize)]
+#[serde(tag = "operation", rename_all = "kebab-case", deny_unknown_fields)]
 enum Commands {
     SeamCarve {
         #[arg(short, long)]
         iterations: usize,
 
-        #[arg(short, long)]
-        direction: char,
+        #[arg(short, long, value_enum)]
+        direction: Direction,
+
+        /// GIF file that records each step, with the removed seam highlighted. In batch mode, a
+        /// naming template like `steps/{name}.gif`
+        #[arg(long)]
+        animation: Option<String>,
+
+        /// Time each step of the animation is shown, in hundredths of a second
+        #[arg(long, default_value_t = 10)]
+        #[serde(default = "default_delay")]
+        delay: u16,
     },
+    #[serde(skip)]
     Statistics {},
+    #[serde(skip)]
+    Preview {},
+    #[serde(skip)]
     Random {},
+    /// Applies the steps of a recipe file to the input, or to each of the given inputs
+    #[serde(skip)]
+    Run {
+        /// TOML file with the steps to apply
+        recipe: String,
+
+        /// Input files, which are processed in batch mode
+        inputs: Vec<String>,
+    },
     Transpose {},
     Rotate {},
     Invert {},
@@ -70,92 +168,603 @@ enum Commands {
         y: usize,
 
         #[arg(short, long)]
-        red: u8,
+        red: u16,
 
         #[arg(short, long)]
-        green: u8,
+        green: u16,
 
         #[arg(short, long)]
-        blue: u8,
+        blue: u16,
     },
 }
 
+/// The direction of the seams that seam carving removes.
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+enum Direction {
+    /// Vertical seams, which make the image narrower
+    #[value(name = "v", alias = "vertical")]
+    #[serde(rename = "v", alias = "vertical")]
+    Vertical,
+    /// Horizontal seams, which make the image lower
+    #[value(name = "h", alias = "horizontal")]
+    #[serde(rename = "h", alias = "horizontal")]
+    Horizontal,
+}
+
+/// Returns the default time each step of an animation is shown, for steps of recipe files.
+fn default_delay() -> u16 {
+    10
+}
+
 fn main() {
-    let cli = Cli::parse();
-    match &cli.command {
-        Some(Commands::SeamCarve {
-            iterations,
-            direction,
-        }) => {
-            let mut image = Image::read(&cli.filename);
-            if *direction == 'v' {
-                image.seam_carve(*iterations, &cli.output, true);
-            } else {
-                image.seam_carve(*iterations, &cli.output, false);
+    let (cli, commands) = parse_commands();
+    let _ = env_logger::try_init();
+    if let Err(err) = run(&cli, &commands) {
+        eprintln!("simp: {err}");
+        process::exit(1);
+    }
+}
+
+/// Parses the command line, which may contain a chain of subcommands. The arguments are split
+/// before each subcommand, and every part is parsed on its own. Statistics, previews, random
+/// images and recipes cannot be chained with other subcommands.
+///
+/// # Returns:
+///   * `(Cli, Vec<Commands>)` - The parsed command line arguments and the subcommands in order
+fn parse_commands() -> (Cli, Vec<Commands>) {
+    let mut args = env::args_os();
+    let program = args.next().unwrap_or_else(|| OsString::from("simp"));
+    let mut parts = split_commands(args).into_iter();
+    let mut first = parts.next().unwrap_or_default();
+    let mut names = Vec::new();
+    let mut rest = Vec::new();
+    for (index, part) in parts.enumerate() {
+        names.push(part[0].to_string_lossy().into_owned());
+        if index == 0 {
+            first.extend(part);
+        } else {
+            rest.push(part);
+        }
+    }
+    let mut cli = Cli::parse_from(iter::once(program.clone()).chain(first));
+    let mut commands: Vec<Commands> = cli.command.take().into_iter().collect();
+    for part in rest {
+        let next = Cli::parse_from(iter::once(program.clone()).chain(part));
+        cli.preview |= next.preview;
+        cli.sixel |= next.sixel;
+        commands.extend(next.command);
+    }
+    if commands.len() > 1 {
+        for (name, command) in names.iter().zip(&commands) {
+            if matches!(
+                command,
+                Commands::Statistics {}
+                    | Commands::Preview {}
+                    | Commands::Random {}
+                    | Commands::Run { .. }
+            ) {
+                Cli::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!("The subcommand '{name}' cannot be chained with other subcommands"),
+                    )
+                    .exit();
+            }
+        }
+    }
+    (cli, commands)
+}
+
+/// Splits the command line arguments before each subcommand. Values of options are never taken
+/// as subcommands, e.g. in `-f mirror`.
+///
+/// # Parameters:
+///   * `args` - The command line arguments, without the name of the program
+///
+/// # Returns:
+///   * `Vec<Vec<OsString>>` - The options before the first subcommand, followed by each
+///     subcommand with its options
+fn split_commands(args: impl Iterator<Item = OsString>) -> Vec<Vec<OsString>> {
+    let cli = Cli::command();
+    let mut current = &cli;
+    let mut parts = vec![Vec::new()];
+    let mut is_value = false;
+    for arg in args {
+        let text = arg.to_string_lossy();
+        // The inputs of recipes may be named like subcommands.
+        if !is_value && current.get_name() != "run" {
+            if let Some(subcommand) = cli.find_subcommand(text.as_ref()) {
+                current = subcommand;
+                parts.push(Vec::new());
             }
         }
-        Some(Commands::Statistics {}) => {
-            let image = Image::read(&cli.filename);
-            image.statistics();
+        is_value = !is_value && takes_value(current, &text);
+        parts.last_mut().unwrap().push(arg);
+    }
+    parts
+}
+
+/// Returns whether an argument is an option that is followed by a value, e.g. `-f` or
+/// `--filename`, but not `--filename=in.ppm`.
+///
+/// # Parameters:
+///   * `command` - The command or subcommand the argument belongs to
+///   * `arg` - The argument
+fn takes_value(command: &clap::Command, arg: &str) -> bool {
+    let long = arg.strip_prefix("--");
+    let mut chars = arg.chars();
+    let short = match (chars.next(), chars.next(), chars.next()) {
+        (Some('-'), Some(short), None) if short != '-' => Some(short),
+        _ => None,
+    };
+    command.get_arguments().any(|option| {
+        option.get_action().takes_values()
+            && (long.is_some() && long == option.get_long()
+                || short.is_some() && short == option.get_short())
+    })
+}
+
+/// Runs the subcommands of the command line, on the input or on every input of a batch.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+///   * `commands` - The subcommands, in the order they are applied
+///
+/// # Returns:
+///   * `Result<(), ImageError>` - Nothing, or the reason why a subcommand failed
+fn run(cli: &Cli, commands: &[Commands]) -> Result<(), ImageError> {
+    let mut inputs = match &cli.batch {
+        Some(pattern) => batch::inputs(pattern)?,
+        None => Vec::new(),
+    };
+    let steps;
+    let commands = match commands {
+        [Commands::Run {
+            recipe,
+            inputs: files,
+        }] => {
+            steps = recipe::load(recipe)?;
+            inputs.extend(files.iter().cloned());
+            &steps[..]
         }
-        Some(Commands::Random {}) => {
-            generate_random_image(&cli.output);
+        commands => commands,
+    };
+    if inputs.is_empty() {
+        return run_on(cli, &cli.filename, &cli.output, commands);
+    }
+    // Statistics and previews are printed instead of written, one input at a time.
+    let prints = matches!(commands, [Commands::Statistics {} | Commands::Preview {}]);
+    if matches!(commands, [Commands::Random {}]) {
+        return Err(ImageError::Unsupported(
+            "Random images cannot be generated in batch mode".to_string(),
+        ));
+    }
+    if !prints && cli.output == "-" {
+        return Err(ImageError::Unsupported(
+            "Batch mode needs an output directory or naming template, given with --output"
+                .to_string(),
+        ));
+    }
+    // Every input records its own animation, which is named with a template like the outputs.
+    for command in commands {
+        if let Commands::SeamCarve {
+            animation: Some(animation),
+            ..
+        } = command
+        {
+            if inputs.len() > 1 && !animation.contains("{name}") {
+                return Err(ImageError::Unsupported(format!(
+                    "With several inputs, the animation {animation} needs a naming template like \
+                     `steps/{{name}}.gif`"
+                )));
+            }
+            if animation.contains("{name}") || animation.contains("{ext}") {
+                batch::outputs(animation, &inputs)?;
+            }
+        }
+    }
+    // The outputs are checked before any input is processed. An output that cannot be finished
+    // is never written, so that failures leave no broken files.
+    let outputs: HashMap<&str, String> = if prints {
+        HashMap::new()
+    } else {
+        let outputs = batch::outputs(&cli.output, &inputs)?;
+        inputs.iter().map(String::as_str).zip(outputs).collect()
+    };
+    let jobs = cli.jobs.unwrap_or_else(batch::default_jobs);
+    let failed = batch::run(&inputs, jobs, |input| {
+        if prints {
+            let _stdout = io::stdout().lock();
+            println!("{input}:");
+            run_on(cli, input, &cli.output, commands)
+        } else {
+            let commands = batch_commands(commands, input)?;
+            run_on(cli, input, &outputs[input], &commands)
+        }
+    });
+    if failed > 0 {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Returns the subcommands for one input of a batch, where the animations of seam carving are
+/// named after the input if they are given as a naming template, e.g. `steps/{name}.gif`.
+///
+/// # Parameters:
+///   * `commands` - The subcommands of the batch
+///   * `input` - The input file
+///
+/// # Returns:
+///   * `Result<Vec<Commands>, ImageError>` - The subcommands, or the reason why the directory of
+///     an animation cannot be created
+fn batch_commands(commands: &[Commands], input: &str) -> Result<Vec<Commands>, ImageError> {
+    let mut commands = commands.to_vec();
+    for command in &mut commands {
+        if let Commands::SeamCarve {
+            animation: Some(animation),
+            ..
+        } = command
+        {
+            if animation.contains("{name}") || animation.contains("{ext}") {
+                *animation = batch::output_name(animation, input)?;
+            }
         }
-        Some(Commands::Transpose {}) => {
-            let image = Image::read(&cli.filename);
-            image.transpose(&cli.output.to_string());
+    }
+    Ok(commands)
+}
+
+/// Runs the subcommands on a single input.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+///   * `input` - The input file, or `-` for the standard input
+///   * `output` - The output file, or `-` for the standard output
+///   * `commands` - The subcommands, in the order they are applied
+///
+/// # Returns:
+///   * `Result<(), ImageError>` - Nothing, or the reason why a subcommand failed
+fn run_on(cli: &Cli, input: &str, output: &str, commands: &[Commands]) -> Result<(), ImageError> {
+    match commands {
+        [Commands::Statistics {}] => {
+            for image in read_frames(cli, input)? {
+                let image = image?;
+                image.statistics();
+                if cli.preview {
+                    print_preview(&image, io::stdout(), cli.sixel)?;
+                }
+            }
         }
-        Some(Commands::Rotate {}) => {
-            let image = Image::read(&cli.filename);
-            image.rotate(&cli.output.to_string());
+        [Commands::Preview {}] => {
+            for image in read_frames(cli, input)? {
+                print_preview(&image?, io::stdout(), cli.sixel)?;
+            }
         }
-        Some(Commands::Invert {}) => {
-            let mut image = Image::read(&cli.filename);
-            image.invert(&cli.output.to_string());
+        [Commands::Random {}] => {
+            let image = convert(cli, output, generate_random_image())?;
+            let mut output = create_output(cli, output)?;
+            output.append(&image)?;
+            output.finish()?;
         }
-        Some(Commands::Mirror {}) => {
-            let image = Image::read(&cli.filename);
-            image.mirror(&cli.output.to_string());
+        // Without subcommands, the images are only converted.
+        commands => process(cli, input, output, commands)?,
+    }
+    Ok(())
+}
+
+/// Reads the images of an input, applies the subcommands to each of them in order, and writes the
+/// results to an output.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+///   * `input` - The input file, or `-` for the standard input
+///   * `output` - The output file, or `-` for the standard output
+///   * `commands` - The subcommands, in the order they are applied
+///
+/// # Returns:
+///   * `Result<(), ImageError>` - Nothing, or the reason why a subcommand failed
+fn process(cli: &Cli, input: &str, output: &str, commands: &[Commands]) -> Result<(), ImageError> {
+    let mut frames = open_frames(cli, input)?;
+    let format = output_format(cli, output);
+    let mut writer = create_output(cli, output)?;
+    let mut animations = commands
+        .iter()
+        .map(|command| match command {
+            Commands::SeamCarve {
+                animation: Some(filename),
+                delay,
+                ..
+            } => Animation::create(filename, *delay)
+                .map(Some)
+                .map_err(|err| write_error(filename, &err)),
+            _ => Ok(None),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // PFM images keep their floating point samples, unless they are converted.
+    if frames.format() == Format::Pfm && format.unwrap_or(Format::Pfm) == Format::Pfm {
+        while let Some(image) = frames.next_float() {
+            let mut image = image?;
+            for (command, animation) in commands.iter().zip(&mut animations) {
+                image = apply_float(command, image, animation.as_mut())?;
+            }
+            writer.append_float(&image)?;
         }
-        Some(Commands::Crop { x1, x2, y1, y2 }) => {
-            let image = Image::read(&cli.filename);
-            image.crop(&cli.output.to_string(), *x1, *x2, *y1, *y2);
+    } else {
+        for image in convert_frames(cli, output, frames) {
+            let mut image = image?;
+            for (command, animation) in commands.iter().zip(&mut animations) {
+                image = apply(command, image, animation.as_mut())?;
+            }
+            writer.append(&image)?;
         }
-        Some(Commands::LandFill {
+    }
+    writer.finish()
+}
+
+/// Applies a subcommand to an image.
+///
+/// # Parameters:
+///   * `command` - The subcommand to apply
+///   * `image` - The image to manipulate
+///   * `animation` - The animation that records the steps of seam carving
+///
+/// # Returns:
+///   * `Result<Image, ImageError>` - The resulting image, or the reason why the subcommand failed
+fn apply(
+    command: &Commands,
+    mut image: Image,
+    animation: Option<&mut Animation>,
+) -> Result<Image, ImageError> {
+    match command {
+        Commands::SeamCarve {
+            iterations,
+            direction,
+            ..
+        } => image.seam_carve(*iterations, *direction == Direction::Vertical, animation)?,
+        Commands::Transpose {} => image = image.transpose(),
+        Commands::Rotate {} => image = image.rotate(),
+        Commands::Invert {} => image.invert(),
+        Commands::Mirror {} => image = image.mirror(),
+        Commands::Crop { x1, x2, y1, y2 } => image = image.crop(*x1, *x2, *y1, *y2)?,
+        Commands::LandFill {
             x,
             y,
             red,
             green,
             blue,
-        }) => {
-            let mut image = Image::read(&cli.filename);
-            image.landfill(&cli.output.to_string(), (*x, *y), (*red, *green, *blue));
-        }
-        None => {}
+        } => image.landfill((*x, *y), (*red, *green, *blue))?,
+        Commands::Statistics {}
+        | Commands::Preview {}
+        | Commands::Random {}
+        | Commands::Run { .. } => {}
+    }
+    Ok(image)
+}
+
+/// Applies a subcommand to a floating point image.
+///
+/// # Parameters:
+///   * `command` - The subcommand to apply
+///   * `image` - The image to manipulate
+///   * `animation` - The animation that records the steps of seam carving
+///
+/// # Returns:
+///   * `Result<FloatImage, ImageError>` - The resulting image, or the reason why the subcommand
+///     failed
+fn apply_float(
+    command: &Commands,
+    mut image: FloatImage,
+    animation: Option<&mut Animation>,
+) -> Result<FloatImage, ImageError> {
+    match command {
+        Commands::SeamCarve {
+            iterations,
+            direction,
+            ..
+        } => image.seam_carve(*iterations, *direction == Direction::Vertical, animation)?,
+        Commands::Transpose {} => image = image.transpose(),
+        Commands::Rotate {} => image = image.rotate(),
+        Commands::Invert {} => image.invert(),
+        Commands::Mirror {} => image = image.mirror(),
+        Commands::Crop { x1, x2, y1, y2 } => image = image.crop(*x1, *x2, *y1, *y2)?,
+        Commands::LandFill {
+            x,
+            y,
+            red,
+            green,
+            blue,
+        } => image.landfill((*x, *y), (*red, *green, *blue))?,
+        Commands::Statistics {}
+        | Commands::Preview {}
+        | Commands::Random {}
+        | Commands::Run { .. } => {}
+    }
+    Ok(image)
+}
+
+/// Reads the frames of an input and applies the requested output format, encoding and scale to
+/// each of them.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+///   * `input` - The input file, or `-` for the standard input
+fn read_frames<'a>(
+    cli: &'a Cli,
+    input: &str,
+) -> Result<impl Iterator<Item = Result<Image, ImageError>> + 'a, ImageError> {
+    Ok(convert_frames(cli, &cli.output, open_frames(cli, input)?))
+}
+
+/// Opens the frames of the input, with the requested layout of raw images and tone mapping of PFM
+/// images.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+///   * `input` - The input file, or `-` for the standard input
+fn open_frames(cli: &Cli, input: &str) -> Result<Frames, ImageError> {
+    // Raw images have no magic bytes, so they are only recognized by their extension.
+    let format = cli
+        .input_format
+        .or_else(|| Format::from_extension(input).filter(|&format| format == Format::Raw));
+    let mut frames = Frames::read(input, format)?;
+    if format == Some(Format::Raw) {
+        let (Some(width), Some(height)) = (cli.raw_width, cli.raw_height) else {
+            return Err(ImageError::Dimensions(
+                "Raw images need --raw-width and --raw-height".to_string(),
+            ));
+        };
+        frames = frames.with_raw_layout(width, height, raw_layout(cli)?);
     }
+    Ok(frames.with_tone_mapping(cli.tone_mapping))
 }
 
-/// Write a random image to a file called `output`.
+/// Applies the requested output format, encoding and scale to each frame.
 ///
 /// # Parameters:
-///   * `output` - A path to the output file
-fn generate_random_image(output: &String) {
+///   * `cli` - The parsed command line arguments
+///   * `output` - The output file, whose extension gives the format if it is not requested
+///   * `frames` - The frames of the input
+fn convert_frames<'a>(
+    cli: &'a Cli,
+    output: &str,
+    frames: Frames,
+) -> impl Iterator<Item = Result<Image, ImageError>> + 'a {
+    let output = output.to_string();
+    frames.map(move |image| convert(cli, &output, image?))
+}
+
+/// Applies the requested output format, encoding and scale to an image. Netpbm images become the
+/// kind of Netpbm image given by the extension of the output file, e.g. a Portable Graymap for
+/// `.pgm`.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+///   * `output` - The output file, whose extension gives the format if it is not requested
+///   * `image` - The image to convert
+fn convert(cli: &Cli, output: &str, mut image: Image) -> Result<Image, ImageError> {
+    if let Some(format) = output_format(cli, output) {
+        image.format = format;
+    }
+    if image.format == Format::Netpbm {
+        image.set_netpbm_kind(output);
+    }
+    if let Some(encoding) = cli.encoding {
+        image.set_encoding(encoding);
+    }
+    if let Some(scale) = cli.scale {
+        image.set_scale(scale)?;
+    }
+    Ok(image)
+}
+
+/// Creates the writer for the output, with the requested previews and layout of raw images.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+///   * `output` - The output file, or `-` for the standard output
+fn create_output(cli: &Cli, output: &str) -> Result<FrameWriter, ImageError> {
+    let filename = output;
+    let mut output = FrameWriter::create(filename).map_err(|err| write_error(filename, &err))?;
+    if cli.preview {
+        output.preview_to(preview_writer(filename), cli.sixel);
+    }
+    if cli.channel_order.is_some() || cli.bit_depth.is_some() || cli.endianness.is_some() {
+        output.set_raw_layout(raw_layout(cli)?);
+    }
+    Ok(output)
+}
+
+/// Returns the error for a file that cannot be written, with the name of the file.
+///
+/// # Parameters:
+///   * `filename` - The location of the file
+///   * `err` - The reason why the file cannot be written
+fn write_error(filename: &str, err: &io::Error) -> ImageError {
+    ImageError::Io(io::Error::new(
+        err.kind(),
+        format!("Could not write {filename}: {err}"),
+    ))
+}
+
+/// Returns the layout of raw images, i.e. the order of the samples, which defaults to rgb, the
+/// bit depth, which defaults to 8, and the endianness, which defaults to big-endian.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+fn raw_layout(cli: &Cli) -> Result<RawLayout, ImageError> {
+    let depth = cli.bit_depth.unwrap_or(8);
+    if depth != 8 && depth != 16 {
+        return Err(ImageError::Unsupported(format!(
+            "Unsupported bit depth {depth}, expected 8 or 16"
+        )));
+    }
+    Ok(RawLayout {
+        order: cli.channel_order.unwrap_or(ChannelOrder::Rgb),
+        depth,
+        endianness: cli.endianness.unwrap_or(Endianness::Big),
+    })
+}
+
+/// Returns the stream for previews of the resulting images: the standard error if the images
+/// are written to the standard output, and the standard output otherwise.
+///
+/// # Parameters:
+///   * `output` - The output file, or `-` for the standard output
+fn preview_writer(output: &str) -> Box<dyn Write> {
+    if output == "-" {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+/// Prints a preview of an image to the terminal.
+///
+/// # Parameters:
+///   * `image` - The image to preview
+///   * `writer` - The stream the preview is printed to
+///   * `sixel` - Whether the preview is printed as sixel graphics
+fn print_preview<W: Write>(image: &Image, mut writer: W, sixel: bool) -> Result<(), ImageError> {
+    terminal::preview(image, &mut writer, sixel)?;
+    Ok(())
+}
+
+/// Returns the output format, given either explicitly or by the extension of the output file.
+///
+/// # Parameters:
+///   * `cli` - The parsed command line arguments
+///   * `output` - The output file, or `-` for the standard output
+fn output_format(cli: &Cli, output: &str) -> Option<Format> {
+    cli.output_format.or_else(|| Format::from_extension(output))
+}
+
+/// Generates a random image with a size of 1000x1000 pixels, as ASCII Portable Pixmap with a
+/// scale of 255.
+fn generate_random_image() -> Image {
     let width: usize = 1000;
     let height: usize = 1000;
     let mut pixels: Vec<Pixel> = Vec::with_capacity(width * height);
     for _ in 0..height {
         for _ in 0..width {
-            let red: u8 = rand::thread_rng().gen();
-            let green: u8 = rand::thread_rng().gen();
-            let blue: u8 = rand::thread_rng().gen();
-            let pixel: Pixel = Pixel { red, green, blue };
+            let red = u16::from(rand::thread_rng().gen::<u8>());
+            let green = u16::from(rand::thread_rng().gen::<u8>());
+            let blue = u16::from(rand::thread_rng().gen::<u8>());
+            let pixel: Pixel = Pixel {
+                red,
+                green,
+                blue,
+                alpha: 255,
+            }
+            .normalize(255);
             pixels.push(pixel);
         }
     }
-    let image: Image = Image {
+    Image {
+        format: Format::Netpbm,
         magic_number: "P3".to_string(),
         scale: 255,
+        comments: Vec::new(),
+        tuple_type: None,
         pixels: DMatrix::from_vec(width, height, pixels),
-    };
-    image.write(output);
+    }
 }
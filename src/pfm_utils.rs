@@ -0,0 +1,278 @@
+/// This crate contains the conversion between floating point images and the Portable FloatMap
+/// (PFM) format, the floating point variant of the Netpbm formats. The header consists of the
+/// magic number PF for color or Pf for grayscale images, the width, the height and a scale, whose
+/// sign gives the byte order of the samples: negative for little-endian, positive for big-endian.
+/// The rows are stored from bottom to top. Images are read in both byte orders, and written
+/// little-endian, like most programs do.
+pub mod pfm {
+    use crate::error_utils::error::ImageError;
+    use crate::float_image_utils::float_image::FloatImage;
+    use crate::image_utils::image::{Format, Image};
+    use crate::pixel_utils::pixel::FloatPixel;
+    use nalgebra::DMatrix;
+
+    /// The header of a PFM file: whether the image is grayscale, its width and height, the
+    /// scale, and the position where the samples start.
+    struct Header {
+        grayscale: bool,
+        width: usize,
+        height: usize,
+        scale: f32,
+        offset: usize,
+    }
+
+    impl Header {
+        /// Returns the number of bytes of the samples.
+        fn data_size(&self) -> Result<usize, ImageError> {
+            let channels = if self.grayscale { 1 } else { 3 };
+            Image::data_size(&[self.width, self.height, channels, 4])
+        }
+    }
+
+    /// Parses the header of a PFM file.
+    ///
+    /// # Parameters:
+    ///  `contents` - The raw bytes of the file
+    ///
+    /// # Returns:
+    ///  `Result<Option<Header>, ImageError>` - The header, `None` if the contents end within
+    ///  the header, or the reason why it is invalid
+    fn parse_header(contents: &[u8]) -> Result<Option<Header>, ImageError> {
+        let grayscale = match contents {
+            [b'P', b'F', ..] => false,
+            [b'P', b'f', ..] => true,
+            [] | [b'P'] => return Ok(None),
+            _ => {
+                return Err(ImageError::Format(
+                    "Missing PF or Pf magic number".to_string(),
+                ))
+            }
+        };
+        let mut position = 2;
+        let mut token = || {
+            while contents.get(position).is_some_and(u8::is_ascii_whitespace) {
+                position += 1;
+            }
+            let start = position;
+            while contents
+                .get(position)
+                .is_some_and(|byte| !byte.is_ascii_whitespace())
+            {
+                position += 1;
+            }
+            // A token at the end of the contents may continue.
+            (position < contents.len())
+                .then(|| std::str::from_utf8(&contents[start..position]).unwrap_or_default())
+        };
+        let (Some(width), Some(height), Some(scale)) = (token(), token(), token()) else {
+            return Ok(None);
+        };
+        let width: usize = width
+            .parse()
+            .map_err(|_| ImageError::Format("Invalid PFM width".to_string()))?;
+        let height: usize = height
+            .parse()
+            .map_err(|_| ImageError::Format("Invalid PFM height".to_string()))?;
+        let scale: f32 = scale
+            .parse()
+            .map_err(|_| ImageError::Format("Invalid PFM scale".to_string()))?;
+        if width == 0 || height == 0 {
+            return Err(ImageError::Dimensions("Invalid PFM dimensions".to_string()));
+        }
+        if scale == 0.0 {
+            return Err(ImageError::Format("Invalid PFM scale".to_string()));
+        }
+        Ok(Some(Header {
+            grayscale,
+            width,
+            height,
+            scale,
+            // A single whitespace character separates the header from the samples.
+            offset: position + 1,
+        }))
+    }
+
+    /// Returns the number of bytes of the PFM image at the start of `contents`, so that a
+    /// stream can be read one image at a time.
+    ///
+    /// # Parameters:
+    ///  `contents` - The bytes of the stream that have been read so far
+    ///
+    /// # Returns:
+    ///  `Result<Option<usize>, ImageError>` - The length, `None` if the contents end within
+    ///  the header, or the reason why the header is invalid
+    pub(crate) fn length(contents: &[u8]) -> Result<Option<usize>, ImageError> {
+        match parse_header(contents)? {
+            Some(header) => Ok(Some(header.offset + header.data_size()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes a PFM file. The contents may continue after the image, e.g. with further images
+    /// of a stream. The magnitude of the scale is not applied to the samples.
+    ///
+    /// # Parameters:
+    ///  `contents` - The raw bytes of the file
+    ///
+    /// # Returns:
+    ///  `Result<(FloatImage, usize), ImageError>` - The image and the number of bytes it
+    ///  occupies, or the reason why it could not be decoded
+    pub fn decode(contents: &[u8]) -> Result<(FloatImage, usize), ImageError> {
+        let header = parse_header(contents)?
+            .ok_or_else(|| ImageError::Format("Unexpected end of the PFM header".to_string()))?;
+        let Header {
+            grayscale,
+            width,
+            height,
+            scale,
+            offset,
+        } = header;
+        let little_endian = scale < 0.0;
+        let channels = if grayscale { 1 } else { 3 };
+        let length = header.data_size()?;
+        let data = contents
+            .get(offset..)
+            .and_then(|data| data.get(..length))
+            .ok_or_else(|| {
+                ImageError::Dimensions("Insufficient data for the specified dimensions".to_string())
+            })?;
+        let sample = |idx: usize| {
+            let bytes = data[4 * idx..4 * idx + 4].try_into().unwrap();
+            if little_endian {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            }
+        };
+        let pixels = DMatrix::from_fn(height, width, |row, col| {
+            let idx = ((height - 1 - row) * width + col) * channels;
+            if grayscale {
+                FloatPixel::from_gray(sample(idx))
+            } else {
+                FloatPixel::from_rgb(sample(idx), sample(idx + 1), sample(idx + 2))
+            }
+        });
+        let image = FloatImage {
+            format: Format::Pfm,
+            magic_number: if grayscale { "Pf" } else { "PF" }.to_string(),
+            scale: u16::MAX,
+            comments: Vec::new(),
+            tuple_type: None,
+            pixels,
+        };
+        Ok((image, offset + length))
+    }
+
+    /// Encodes a floating point image as little-endian PFM file, with a scale of 1. Images with
+    /// the magic number Pf are encoded as grayscale, all others as color images.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to encode
+    ///
+    /// # Returns:
+    ///  `Vec<u8>` - The contents of the file
+    pub fn encode(image: &FloatImage) -> Vec<u8> {
+        let (height, width) = image.pixels.shape();
+        let grayscale = image.magic_number == "Pf";
+        let magic_number = if grayscale { "Pf" } else { "PF" };
+        let mut contents = format!("{magic_number}\n{width} {height}\n-1.0\n").into_bytes();
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let pixel = image.pixels[(y, x)];
+                if grayscale {
+                    contents.extend_from_slice(&pixel.gray().to_le_bytes());
+                } else {
+                    for sample in [pixel.red, pixel.green, pixel.blue] {
+                        contents.extend_from_slice(&sample.to_le_bytes());
+                    }
+                }
+            }
+        }
+        contents
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Returns a PFM file of 2 by 2 pixels in the given byte order, whose rows are stored
+        /// from bottom to top.
+        fn pfm(magic_number: &str, little_endian: bool, samples: &[f32]) -> Vec<u8> {
+            let scale = if little_endian { "-1.0" } else { "1.0" };
+            let mut contents = format!("{magic_number}\n2 2\n{scale}\n").into_bytes();
+            for sample in samples {
+                if little_endian {
+                    contents.extend_from_slice(&sample.to_le_bytes());
+                } else {
+                    contents.extend_from_slice(&sample.to_be_bytes());
+                }
+            }
+            contents
+        }
+
+        #[test]
+        fn reads_both_byte_orders() {
+            let samples = [
+                0.5,
+                1.0,
+                2.0,
+                1e9,
+                -1.0,
+                f32::INFINITY,
+                0.0,
+                0.25,
+                0.125,
+                3.0,
+                4.0,
+                5.0,
+            ];
+            for little_endian in [true, false] {
+                let contents = pfm("PF", little_endian, &samples);
+                let (image, length) = decode(&contents).unwrap();
+                assert_eq!(length, contents.len());
+                assert_eq!(image.pixels[(1, 0)], FloatPixel::from_rgb(0.5, 1.0, 2.0));
+                assert_eq!(
+                    image.pixels[(1, 1)],
+                    FloatPixel::from_rgb(1e9, -1.0, f32::INFINITY)
+                );
+                assert_eq!(image.pixels[(0, 1)], FloatPixel::from_rgb(3.0, 4.0, 5.0));
+            }
+        }
+
+        #[test]
+        fn round_trips_little_endian() {
+            for (magic_number, samples) in [
+                (
+                    "PF",
+                    &[
+                        0.5, 1.0, 2.0, 1e9, -1.0, 0.0, 0.0, 0.25, 0.125, 3.0, 4.0, 5.0,
+                    ][..],
+                ),
+                ("Pf", &[0.5, 1e9, -1.0, 7.0]),
+            ] {
+                let contents = pfm(magic_number, true, samples);
+                let (image, _) = decode(&contents).unwrap();
+                assert_eq!(image.magic_number, magic_number);
+                assert_eq!(encode(&image), contents);
+            }
+        }
+
+        #[test]
+        fn rejects_invalid_and_oversized_images() {
+            let mut truncated = pfm("Pf", true, &[0.5, 1.0, 2.0, 3.0]);
+            truncated.pop();
+            for contents in [
+                &truncated[..],
+                b"PF\n0 2\n-1.0\n",
+                b"PF\n2 2\n0.0\n",
+                b"PF\nx 2\n-1.0\n",
+                b"P6\n2 2\n-1.0\n",
+                b"PF\n2 2",
+                format!("PF\n{} {}\n-1.0\n", usize::MAX, usize::MAX).as_bytes(),
+            ] {
+                let result = decode(contents);
+                assert!(result.is_err(), "{}", String::from_utf8_lossy(contents));
+            }
+        }
+    }
+}
@@ -1,49 +1,275 @@
+/// This crate contains the pixels of images, their conversion between 16-bit and floating point
+/// samples, and their color differences.
 pub mod pixel {
+    use nalgebra::Scalar;
     use num_traits::Zero;
 
+    /// A pixel with red, green and blue samples and an `alpha` sample for its opacity. The
+    /// samples are 16 bits wide, so that images with a scale of up to 65535 keep their full
+    /// precision. Images normalize their samples to the full range `0..=u16::MAX`, independent of
+    /// their scale, so an opaque pixel has an alpha of `u16::MAX`.
     #[derive(Clone, Copy, PartialEq, Debug)]
     pub struct Pixel {
-        pub red: u8,
-        pub green: u8,
-        pub blue: u8,
+        /// The red sample
+        pub red: u16,
+        /// The green sample
+        pub green: u16,
+        /// The blue sample
+        pub blue: u16,
+        /// The opacity, from transparent at 0 to opaque at `u16::MAX`
+        pub alpha: u16,
     }
 
     impl Pixel {
+        /// Creates an opaque pixel from its colors.
+        pub fn from_rgb(red: u16, green: u16, blue: u16) -> Pixel {
+            Pixel {
+                red,
+                green,
+                blue,
+                alpha: u16::MAX,
+            }
+        }
+
+        /// Creates an opaque gray pixel, i.e. a pixel whose colors all have the value `gray`.
+        pub fn from_gray(gray: u16) -> Pixel {
+            Pixel::from_rgb(gray, gray, gray)
+        }
+
+        /// Creates a pixel from a bitmap bit, where a set bit stands for black.
+        pub fn from_bit(bit: bool) -> Pixel {
+            if bit {
+                Pixel::from_gray(0)
+            } else {
+                Pixel::from_gray(u16::MAX)
+            }
+        }
+
+        /// Returns the gray value of a pixel, defined as the average of its colors.
+        #[allow(clippy::cast_possible_truncation)]
+        pub fn gray(&self) -> u16 {
+            ((u32::from(self.red) + u32::from(self.green) + u32::from(self.blue)) / 3) as u16
+        }
+
+        /// Returns the bitmap bit of a pixel, i.e. true if the pixel is closer to black than to
+        /// white.
+        pub fn bit(&self) -> bool {
+            self.gray() <= u16::MAX / 2
+        }
+
+        /// Scales the samples of a pixel from the range `0..=scale` to the full range
+        /// `0..=u16::MAX`.
+        pub fn normalize(self, scale: u16) -> Pixel {
+            Pixel {
+                red: rescale(self.red, scale, u16::MAX),
+                green: rescale(self.green, scale, u16::MAX),
+                blue: rescale(self.blue, scale, u16::MAX),
+                alpha: rescale(self.alpha, scale, u16::MAX),
+            }
+        }
+
+        /// Scales the samples of a pixel from the full range `0..=u16::MAX` to the range
+        /// `0..=scale`.
+        pub fn denormalize(self, scale: u16) -> Pixel {
+            Pixel {
+                red: rescale(self.red, u16::MAX, scale),
+                green: rescale(self.green, u16::MAX, scale),
+                blue: rescale(self.blue, u16::MAX, scale),
+                alpha: rescale(self.alpha, u16::MAX, scale),
+            }
+        }
+    }
+
+    /// Pixels whose color differences are the energy of seam carving.
+    pub trait ColorDiff: Copy {
+        /// Computes the color difference between two pixels, which is zero for equal pixels.
+        fn color_diff(pixel1: Self, pixel2: Self) -> u64;
+    }
+
+    /// The pixels that images consist of. Every kind of pixel can be converted to and from
+    /// `Pixel`, so that images of any kind can be read, written, previewed and recorded.
+    pub trait ImagePixel: Scalar + Copy + Zero + ColorDiff {
+        /// The number of samples of a pixel, i.e. its colors and its opacity, if it has one.
+        const CHANNELS: usize;
+
+        /// Converts a pixel with 16-bit samples to this kind of pixel.
+        fn from_pixel(pixel: Pixel) -> Self;
+
+        /// Converts the pixel to a pixel with 16-bit samples.
+        fn to_pixel(self) -> Pixel;
+
+        /// Inverts the colors of the pixel, while keeping its opacity.
+        fn invert(&mut self);
+
+        /// Returns the pixel with the colors of another pixel, while keeping its opacity.
+        fn with_colors(self, colors: Self) -> Self;
+    }
+
+    impl ImagePixel for Pixel {
+        const CHANNELS: usize = 4;
+
+        fn from_pixel(pixel: Pixel) -> Pixel {
+            pixel
+        }
+
+        fn to_pixel(self) -> Pixel {
+            self
+        }
+
+        fn invert(&mut self) {
+            self.red = u16::MAX - self.red;
+            self.green = u16::MAX - self.green;
+            self.blue = u16::MAX - self.blue;
+        }
+
+        fn with_colors(self, colors: Pixel) -> Pixel {
+            Pixel {
+                alpha: self.alpha,
+                ..colors
+            }
+        }
+    }
+
+    impl ColorDiff for Pixel {
         /// Computes color differences between two pixels, by subtracting their values and squaring
-        /// them.
+        /// them. Differences in opacity count like differences in color.
         #[allow(clippy::cast_sign_loss)]
-        pub fn color_diff(pixel1: Pixel, pixel2: Pixel) -> u32 {
-            let red_diff = i32::from(pixel1.red) - i32::from(pixel2.red);
-            let green_diff = i32::from(pixel1.green) - i32::from(pixel2.green);
-            let blue_diff = i32::from(pixel1.blue) - i32::from(pixel2.blue);
+        fn color_diff(pixel1: Pixel, pixel2: Pixel) -> u64 {
+            let red_diff = i64::from(pixel1.red) - i64::from(pixel2.red);
+            let green_diff = i64::from(pixel1.green) - i64::from(pixel2.green);
+            let blue_diff = i64::from(pixel1.blue) - i64::from(pixel2.blue);
+            let alpha_diff = i64::from(pixel1.alpha) - i64::from(pixel2.alpha);
             let red_diff_squared = red_diff * red_diff;
             let green_diff_squared = green_diff * green_diff;
             let blue_diff_squared = blue_diff * blue_diff;
-            (red_diff_squared + green_diff_squared + blue_diff_squared) as u32
+            let alpha_diff_squared = alpha_diff * alpha_diff;
+            (red_diff_squared + green_diff_squared + blue_diff_squared + alpha_diff_squared) as u64
+        }
+    }
+
+    /// A pixel of a high dynamic range image, with red, green and blue samples as floating point
+    /// numbers. The samples are linear light, where 1.0 is the white of the display, but may be
+    /// brighter. Pixels of high dynamic range images have no opacity.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub struct FloatPixel {
+        /// The red sample
+        pub red: f32,
+        /// The green sample
+        pub green: f32,
+        /// The blue sample
+        pub blue: f32,
+    }
+
+    impl FloatPixel {
+        /// Creates a pixel from its colors.
+        pub fn from_rgb(red: f32, green: f32, blue: f32) -> FloatPixel {
+            FloatPixel { red, green, blue }
+        }
+
+        /// Creates a gray pixel, i.e. a pixel whose colors all have the value `gray`.
+        pub fn from_gray(gray: f32) -> FloatPixel {
+            FloatPixel::from_rgb(gray, gray, gray)
+        }
+
+        /// Returns the gray value of a pixel, defined as the average of its colors.
+        pub fn gray(&self) -> f32 {
+            (self.red + self.green + self.blue) / 3.0
+        }
+    }
+
+    impl ImagePixel for FloatPixel {
+        const CHANNELS: usize = 3;
+
+        /// Converts the colors of a pixel from the sRGB curve of images to linear light. The
+        /// opacity is dropped.
+        fn from_pixel(pixel: Pixel) -> FloatPixel {
+            let sample = |sample: u16| srgb_to_linear(f32::from(sample) / f32::from(u16::MAX));
+            FloatPixel::from_rgb(sample(pixel.red), sample(pixel.green), sample(pixel.blue))
         }
 
-        /// Inverts the colors of a pixel.
-        pub fn invert(&mut self) {
-            self.red = 255 - self.red;
-            self.green = 255 - self.green;
-            self.blue = 255 - self.blue;
+        /// Converts the colors of a pixel from linear light to the sRGB curve of images, with
+        /// everything brighter than white clamped to white.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        fn to_pixel(self) -> Pixel {
+            let sample = |sample: f32| {
+                let value = linear_to_srgb(sample.clamp(0.0, 1.0));
+                (value * f32::from(u16::MAX)).round() as u16
+            };
+            Pixel::from_rgb(sample(self.red), sample(self.green), sample(self.blue))
+        }
+
+        /// Inverts the colors of a pixel relative to the white of the display, so that colors
+        /// brighter than white become black.
+        fn invert(&mut self) {
+            self.red = (1.0 - self.red).max(0.0);
+            self.green = (1.0 - self.green).max(0.0);
+            self.blue = (1.0 - self.blue).max(0.0);
+        }
+
+        /// Returns the other pixel, as floating point pixels have no opacity.
+        fn with_colors(self, colors: FloatPixel) -> FloatPixel {
+            colors
+        }
+    }
+
+    impl ColorDiff for FloatPixel {
+        /// Computes color differences between two pixels like for 16-bit pixels, i.e. with the
+        /// white of the display as 65535, so that both kinds of images are carved alike. Very
+        /// large differences saturate.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        fn color_diff(pixel1: FloatPixel, pixel2: FloatPixel) -> u64 {
+            let diff = |sample1: f32, sample2: f32| {
+                let diff = f64::from(sample1 - sample2) * f64::from(u16::MAX);
+                diff * diff
+            };
+            (diff(pixel1.red, pixel2.red)
+                + diff(pixel1.green, pixel2.green)
+                + diff(pixel1.blue, pixel2.blue)) as u64
+        }
+    }
+
+    /// Converts a sample from the sRGB curve of images to linear light.
+    pub fn srgb_to_linear(sample: f32) -> f32 {
+        if sample <= 0.04045 {
+            sample / 12.92
+        } else {
+            ((sample + 0.055) / 1.055).powf(2.4)
         }
     }
 
+    /// Converts a sample from linear light to the sRGB curve of images.
+    pub fn linear_to_srgb(sample: f32) -> f32 {
+        if sample <= 0.003_130_8 {
+            sample * 12.92
+        } else {
+            1.055 * sample.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    /// Scales a sample from the range `0..=from` to the range `0..=to`, rounding to the nearest
+    /// value. Scaling a sample up and down again yields the original sample. Samples above
+    /// `from` are clamped, so that they cannot wrap around.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn rescale(sample: u16, from: u16, to: u16) -> u16 {
+        let (sample, from, to) = (u32::from(sample.min(from)), u32::from(from), u32::from(to));
+        ((sample * to + from / 2) / from) as u16
+    }
+
     /// Implements the Zero trait for Pixel.
     impl Zero for Pixel {
-        /// Returns a pixel with zero values for rgb colors.
+        /// Returns a pixel with zero values for rgb colors and alpha.
         fn zero() -> Self {
             Self {
                 red: 0,
                 green: 0,
                 blue: 0,
+                alpha: 0,
             }
         }
 
-        /// Returns true if the pixel colors are only zero.
+        /// Returns true if the pixel colors and alpha are only zero.
         fn is_zero(&self) -> bool {
-            self.red == 0 && self.green == 0 && self.blue == 0
+            self.red == 0 && self.green == 0 && self.blue == 0 && self.alpha == 0
         }
     }
 
@@ -51,13 +277,41 @@ pub mod pixel {
     impl std::ops::Add for Pixel {
         type Output = Self;
 
-        /// Adds the colors of other to self.
+        /// Adds the colors and alpha of other to self.
         fn add(self, other: Self) -> Self {
             Self {
                 red: self.red.saturating_add(other.red),
                 green: self.green.saturating_add(other.green),
                 blue: self.blue.saturating_add(other.blue),
+                alpha: self.alpha.saturating_add(other.alpha),
             }
         }
     }
+
+    /// Implements the Zero trait for FloatPixel.
+    impl Zero for FloatPixel {
+        /// Returns a black pixel.
+        fn zero() -> Self {
+            FloatPixel::from_gray(0.0)
+        }
+
+        /// Returns true if the pixel is black.
+        fn is_zero(&self) -> bool {
+            self.red == 0.0 && self.green == 0.0 && self.blue == 0.0
+        }
+    }
+
+    /// Implements the Add trait for FloatPixel.
+    impl std::ops::Add for FloatPixel {
+        type Output = Self;
+
+        /// Adds the colors of other to self.
+        fn add(self, other: Self) -> Self {
+            FloatPixel::from_rgb(
+                self.red + other.red,
+                self.green + other.green,
+                self.blue + other.blue,
+            )
+        }
+    }
 }
@@ -0,0 +1,103 @@
+/// This crate contains the conversion between images and the Portable Network Graphics (PNG)
+/// format. Decoding and encoding, i.e. the zlib compression and the row filters, is done by the
+/// `png` crate.
+pub mod png {
+    use crate::error_utils::error::ImageError;
+    use crate::header_utils::header::TupleType;
+    use crate::image_utils::image::{Format, Image};
+    use ::png::Transformations;
+    use ::png::{BitDepth, ColorType, Decoder, Encoder};
+    use std::io::Cursor;
+
+    /// The keyword of the text chunks that store the comments of an image.
+    const COMMENT: &str = "Comment";
+
+    /// Decodes a PNG file. Palettes and bit depths below 8 are expanded, so that the image has
+    /// gray, gray and alpha, rgb, or rgb and alpha samples with 8 or 16 bits. The image gets the
+    /// magic number of the matching Netpbm format, i.e. P5 for gray, P6 for rgb, and P7 if there
+    /// is an alpha channel. Text chunks with the keyword "Comment" become the comments of the
+    /// image.
+    ///
+    /// # Parameters:
+    ///  `contents` - The raw bytes of the file
+    ///
+    /// # Returns:
+    ///  `Result<Image, ImageError>` - The image or the reason why it could not be decoded
+    pub fn decode(contents: &[u8]) -> Result<Image, ImageError> {
+        let mut decoder = Decoder::new(Cursor::new(contents));
+        decoder.set_transformations(Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size().unwrap_or(0)];
+        let frame = reader.next_frame(&mut data)?;
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let scale = match frame.bit_depth {
+            BitDepth::Sixteen => u16::MAX,
+            _ => 255,
+        };
+        let (magic_number, tuple_type) = match frame.color_type {
+            ColorType::Grayscale => ("P5", None),
+            ColorType::GrayscaleAlpha => ("P7", Some(("GRAYSCALE_ALPHA", 2))),
+            ColorType::Rgba => ("P7", Some(("RGB_ALPHA", 4))),
+            _ => ("P6", None),
+        };
+        let channels = tuple_type.map_or(frame.color_type.samples(), |(_, depth)| depth);
+        let pixels = Image::parse_binary_pixels(&data, width, height, channels, scale)?;
+        let comments = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .filter(|chunk| chunk.keyword == COMMENT)
+            .map(|chunk| chunk.text.clone())
+            .collect();
+        Ok(Image {
+            format: Format::Png,
+            magic_number: magic_number.to_string(),
+            scale,
+            comments,
+            tuple_type: tuple_type.map(|(name, depth)| TupleType {
+                name: name.to_string(),
+                depth,
+            }),
+            pixels,
+        })
+    }
+
+    /// Encodes an image as PNG file. The color type follows the number of samples of the image,
+    /// e.g. graymaps are encoded as gray and images with alpha channel as rgb and alpha. Images
+    /// with a scale above 255 are encoded with 16 bits per sample, all others with 8 bits.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to encode
+    ///
+    /// # Returns:
+    ///  `Result<Vec<u8>, ImageError>` - The contents of the file, or the reason why it could
+    ///  not be encoded
+    pub fn encode(image: &Image) -> Result<Vec<u8>, ImageError> {
+        let channels = image.channels();
+        let (depth, scale) = if image.scale > 255 {
+            (BitDepth::Sixteen, u16::MAX)
+        } else {
+            (BitDepth::Eight, 255)
+        };
+        let color_type = match channels {
+            1 => ColorType::Grayscale,
+            2 => ColorType::GrayscaleAlpha,
+            3 => ColorType::Rgb,
+            _ => ColorType::Rgba,
+        };
+        let too_large = |_| ImageError::Dimensions("Image too large for PNG".to_string());
+        let width = u32::try_from(image.pixels.ncols()).map_err(too_large)?;
+        let height = u32::try_from(image.pixels.nrows()).map_err(too_large)?;
+        let mut contents = Vec::new();
+        let mut encoder = Encoder::new(&mut contents, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(depth);
+        for comment in &image.comments {
+            encoder.add_text_chunk(COMMENT.to_string(), comment.clone())?;
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&image.binary_pixels(channels, scale))?;
+        writer.finish()?;
+        Ok(contents)
+    }
+}
@@ -0,0 +1,337 @@
+/// This crate contains the conversion between images and the Quite OK Image (QOI) format, a
+/// simple lossless format with fast encoding and decoding.
+pub mod qoi {
+    use crate::error_utils::error::ImageError;
+    use crate::header_utils::header::TupleType;
+    use crate::image_utils::image::{Format, Image};
+    use crate::pixel_utils::pixel::Pixel;
+    use nalgebra::DMatrix;
+
+    /// The size of the header, which starts with "qoif".
+    const HEADER_SIZE: usize = 14;
+
+    /// The stream ends with seven zero bytes and a one byte.
+    const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+    /// The pixel is stored as index into the array of previously seen pixels.
+    const OP_INDEX: u8 = 0x00;
+
+    /// The pixel is stored as small difference to the previous pixel.
+    const OP_DIFF: u8 = 0x40;
+
+    /// The pixel is stored as difference to the previous pixel, relative to the green difference.
+    const OP_LUMA: u8 = 0x80;
+
+    /// The previous pixel is repeated.
+    const OP_RUN: u8 = 0xc0;
+
+    /// The pixel is stored with its red, green and blue samples.
+    const OP_RGB: u8 = 0xfe;
+
+    /// The pixel is stored with its red, green, blue and alpha samples.
+    const OP_RGBA: u8 = 0xff;
+
+    /// The mask of the two bit tags.
+    const MASK: u8 = 0xc0;
+
+    /// The longest run of a single OP_RUN byte, which is also the most pixels a byte can hold.
+    const MAX_RUN: u8 = 62;
+
+    /// Returns the position of a pixel in the array of previously seen pixels.
+    fn hash([red, green, blue, alpha]: [u8; 4]) -> usize {
+        (usize::from(red) * 3
+            + usize::from(green) * 5
+            + usize::from(blue) * 7
+            + usize::from(alpha) * 11)
+            % 64
+    }
+
+    /// Decodes a QOI file. The image gets the magic number P6, or P7 if it has an alpha channel.
+    ///
+    /// # Parameters:
+    ///  `contents` - The raw bytes of the file
+    ///
+    /// # Returns:
+    ///  `Result<Image, ImageError>` - The image or the reason why it could not be decoded
+    pub fn decode(contents: &[u8]) -> Result<Image, ImageError> {
+        if contents.len() < HEADER_SIZE || !contents.starts_with(b"qoif") {
+            return Err(ImageError::Format("Missing qoif signature".to_string()));
+        }
+        let width = u32::from_be_bytes(contents[4..8].try_into().unwrap()) as usize;
+        let height = u32::from_be_bytes(contents[8..12].try_into().unwrap()) as usize;
+        let channels = contents[12];
+        if width == 0 || height == 0 {
+            return Err(ImageError::Dimensions("Invalid QOI dimensions".to_string()));
+        }
+        if channels != 3 && channels != 4 {
+            return Err(ImageError::Format(
+                "Invalid number of QOI channels".to_string(),
+            ));
+        }
+        let size = Image::data_size(&[width, height])?;
+        let data = &contents[HEADER_SIZE..];
+        if size / usize::from(MAX_RUN) > data.len() {
+            return Err(ImageError::Dimensions(
+                "Insufficient data for the specified dimensions".to_string(),
+            ));
+        }
+        let mut data = data.iter().copied();
+        let mut next = || {
+            data.next()
+                .ok_or_else(|| ImageError::Format("Unexpected end of the QOI data".to_string()))
+        };
+        let mut seen = [[0u8; 4]; 64];
+        let mut previous = [0, 0, 0, 255];
+        let mut run = 0;
+        let mut pixels = Vec::with_capacity(size);
+        while pixels.len() < size {
+            if run > 0 {
+                run -= 1;
+            } else {
+                let byte = next()?;
+                let [red, green, blue, alpha] = previous;
+                previous = match byte {
+                    OP_RGB => [next()?, next()?, next()?, alpha],
+                    OP_RGBA => [next()?, next()?, next()?, next()?],
+                    _ => match byte & MASK {
+                        OP_INDEX => seen[usize::from(byte)],
+                        OP_DIFF => [
+                            red.wrapping_add((byte >> 4) & 0x03).wrapping_sub(2),
+                            green.wrapping_add((byte >> 2) & 0x03).wrapping_sub(2),
+                            blue.wrapping_add(byte & 0x03).wrapping_sub(2),
+                            alpha,
+                        ],
+                        OP_LUMA => {
+                            let green_diff = (byte & 0x3f).wrapping_sub(32);
+                            let second = next()?;
+                            [
+                                red.wrapping_add(green_diff)
+                                    .wrapping_add(second >> 4)
+                                    .wrapping_sub(8),
+                                green.wrapping_add(green_diff),
+                                blue.wrapping_add(green_diff)
+                                    .wrapping_add(second & 0x0f)
+                                    .wrapping_sub(8),
+                                alpha,
+                            ]
+                        }
+                        _ => {
+                            run = byte & 0x3f;
+                            previous
+                        }
+                    },
+                };
+                seen[hash(previous)] = previous;
+            }
+            let [red, green, blue, alpha] = previous.map(u16::from);
+            pixels.push(
+                Pixel {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                }
+                .normalize(255),
+            );
+        }
+        let has_alpha = channels == 4;
+        Ok(Image {
+            format: Format::Qoi,
+            magic_number: if has_alpha { "P7" } else { "P6" }.to_string(),
+            scale: 255,
+            comments: Vec::new(),
+            tuple_type: has_alpha.then(|| TupleType {
+                name: "RGB_ALPHA".to_string(),
+                depth: 4,
+            }),
+            pixels: DMatrix::from_row_slice(height, width, &pixels),
+        })
+    }
+
+    /// Encodes an image as QOI file with 8 bits per sample. Images with an alpha channel are
+    /// encoded with four channels, all others with three.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to encode
+    ///
+    /// # Returns:
+    ///  `Result<Vec<u8>, ImageError>` - The contents of the file, or the reason why it could
+    ///  not be encoded
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn encode(image: &Image) -> Result<Vec<u8>, ImageError> {
+        let (height, width) = image.pixels.shape();
+        let channels: u8 = if matches!(image.channels(), 2 | 4) {
+            4
+        } else {
+            3
+        };
+        let to_u32 = |value: usize| {
+            u32::try_from(value)
+                .map_err(|_| ImageError::Dimensions("Image too large for QOI".to_string()))
+        };
+        let mut contents = Vec::with_capacity(HEADER_SIZE + width * height + END_MARKER.len());
+        contents.extend_from_slice(b"qoif");
+        contents.extend_from_slice(&to_u32(width)?.to_be_bytes());
+        contents.extend_from_slice(&to_u32(height)?.to_be_bytes());
+        // The samples are sRGB with linear alpha.
+        contents.extend_from_slice(&[channels, 0]);
+        let mut seen = [[0u8; 4]; 64];
+        let mut previous = [0, 0, 0, 255];
+        let mut run = 0;
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = image.pixels[(y, x)].denormalize(255);
+                let alpha = if channels == 4 {
+                    pixel.alpha as u8
+                } else {
+                    255
+                };
+                let current = [pixel.red as u8, pixel.green as u8, pixel.blue as u8, alpha];
+                if current == previous {
+                    run += 1;
+                    if run == MAX_RUN {
+                        contents.push(OP_RUN | (run - 1));
+                        run = 0;
+                    }
+                    continue;
+                }
+                if run > 0 {
+                    contents.push(OP_RUN | (run - 1));
+                    run = 0;
+                }
+                let index = hash(current);
+                if seen[index] == current {
+                    contents.push(OP_INDEX | index as u8);
+                } else if current[3] != previous[3] {
+                    contents.push(OP_RGBA);
+                    contents.extend_from_slice(&current);
+                } else {
+                    let red_diff = current[0].wrapping_sub(previous[0]) as i8;
+                    let green_diff = current[1].wrapping_sub(previous[1]) as i8;
+                    let blue_diff = current[2].wrapping_sub(previous[2]) as i8;
+                    let red_green = red_diff.wrapping_sub(green_diff);
+                    let blue_green = blue_diff.wrapping_sub(green_diff);
+                    if (-2..2).contains(&red_diff)
+                        && (-2..2).contains(&green_diff)
+                        && (-2..2).contains(&blue_diff)
+                    {
+                        contents.push(
+                            OP_DIFF
+                                | ((red_diff + 2) as u8) << 4
+                                | ((green_diff + 2) as u8) << 2
+                                | (blue_diff + 2) as u8,
+                        );
+                    } else if (-32..32).contains(&green_diff)
+                        && (-8..8).contains(&red_green)
+                        && (-8..8).contains(&blue_green)
+                    {
+                        contents.push(OP_LUMA | (green_diff + 32) as u8);
+                        contents.push(((red_green + 8) as u8) << 4 | (blue_green + 8) as u8);
+                    } else {
+                        contents.push(OP_RGB);
+                        contents.extend_from_slice(&current[..3]);
+                    }
+                }
+                seen[index] = current;
+                previous = current;
+            }
+        }
+        if run > 0 {
+            contents.push(OP_RUN | (run - 1));
+        }
+        contents.extend_from_slice(&END_MARKER);
+        Ok(contents)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Returns an image whose pixels exercise all operations of the encoder: long runs,
+        /// small and medium differences, large jumps and pixels seen before. With `alpha`,
+        /// the opacity changes as well.
+        fn image(alpha: bool) -> Image {
+            let (width, height) = (80, 3);
+            let pixels = DMatrix::from_fn(height, width, |y, x| {
+                let (red, green, blue) = match (y, x) {
+                    (0, _) => (10, 20, 30),
+                    (1, x) if x % 2 == 0 => (10 + x, 20 + x, 30 + x),
+                    (1, x) => (x * 3, x * 2 + 7, 255 - x),
+                    (_, x) => ((x * 97) % 256, (x * 31) % 256, x % 4),
+                };
+                let opacity = if alpha { (x * 13 % 256) as u16 } else { 255 };
+                Pixel {
+                    red: red as u16,
+                    green: green as u16,
+                    blue: blue as u16,
+                    alpha: opacity,
+                }
+                .normalize(255)
+            });
+            Image {
+                format: Format::Qoi,
+                magic_number: if alpha { "P7" } else { "P6" }.to_string(),
+                scale: 255,
+                comments: Vec::new(),
+                tuple_type: alpha.then(|| TupleType {
+                    name: "RGB_ALPHA".to_string(),
+                    depth: 4,
+                }),
+                pixels,
+            }
+        }
+
+        #[test]
+        fn rgb_and_rgba_round_trips() {
+            for alpha in [false, true] {
+                let image = image(alpha);
+                let contents = encode(&image).unwrap();
+                assert_eq!(contents[12], if alpha { 4 } else { 3 });
+                assert!(contents.ends_with(&END_MARKER));
+                let decoded = decode(&contents).unwrap();
+                assert_eq!(decoded.magic_number, image.magic_number);
+                assert_eq!(decoded.tuple_type, image.tuple_type);
+                assert_eq!(decoded.pixels, image.pixels);
+            }
+        }
+
+        #[test]
+        fn encodes_runs() {
+            let mut image = image(false);
+            image.pixels = DMatrix::from_element(2, 100, Pixel::from_gray(0));
+            let contents = encode(&image).unwrap();
+            // Black repeats the initial previous pixel, so all pixels are runs of at most 62.
+            let data = &contents[HEADER_SIZE..contents.len() - END_MARKER.len()];
+            assert_eq!(data, [OP_RUN | 61, OP_RUN | 61, OP_RUN | 61, OP_RUN | 13]);
+            assert_eq!(decode(&contents).unwrap().pixels, image.pixels);
+        }
+
+        #[test]
+        fn rejects_invalid_and_oversized_images() {
+            let header = |width: u32, height: u32, channels: u8| {
+                let mut contents = b"qoif".to_vec();
+                contents.extend_from_slice(&width.to_be_bytes());
+                contents.extend_from_slice(&height.to_be_bytes());
+                contents.extend_from_slice(&[channels, 0]);
+                contents
+            };
+            let mut truncated = header(2, 1, 3);
+            truncated.extend_from_slice(&[OP_RGB, 1, 2]);
+            let mut huge = header(100_000, 100_000, 3);
+            huge.extend_from_slice(&END_MARKER);
+            let mut largest = header(u32::MAX, u32::MAX, 4);
+            largest.extend_from_slice(&END_MARKER);
+            for contents in [
+                b"qoif".to_vec(),
+                b"qoff\0\0\0\x01\0\0\0\x01\x03\0".to_vec(),
+                header(0, 1, 3),
+                header(1, 1, 5),
+                truncated,
+                huge,
+                largest,
+            ] {
+                assert!(decode(&contents).is_err());
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+/// This crate contains the conversion between images and raw pixel data without header, as
+/// dumped by cameras or simulations. As the data does not describe itself, the size of the image
+/// and the layout of the samples have to be given.
+pub mod raw {
+    use crate::error_utils::error::ImageError;
+    use crate::header_utils::header::TupleType;
+    use crate::image_utils::image::{Format, Image};
+    use crate::pixel_utils::pixel::Pixel;
+    use nalgebra::DMatrix;
+
+    /// The samples of a pixel, in the order they are stored.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+    pub enum ChannelOrder {
+        /// A gray sample
+        Gray,
+        /// A gray and an alpha sample
+        GrayAlpha,
+        /// Red, green and blue samples
+        Rgb,
+        /// Blue, green and red samples
+        Bgr,
+        /// Red, green, blue and alpha samples
+        Rgba,
+        /// Blue, green, red and alpha samples
+        Bgra,
+        /// Alpha, red, green and blue samples
+        Argb,
+        /// Alpha, blue, green and red samples
+        Abgr,
+    }
+
+    impl ChannelOrder {
+        /// Returns the samples as characters: `y` for gray, and `r`, `g`, `b` and `a` for red,
+        /// green, blue and alpha.
+        fn samples(self) -> &'static str {
+            match self {
+                ChannelOrder::Gray => "y",
+                ChannelOrder::GrayAlpha => "ya",
+                ChannelOrder::Rgb => "rgb",
+                ChannelOrder::Bgr => "bgr",
+                ChannelOrder::Rgba => "rgba",
+                ChannelOrder::Bgra => "bgra",
+                ChannelOrder::Argb => "argb",
+                ChannelOrder::Abgr => "abgr",
+            }
+        }
+
+        /// Returns the number of samples per pixel.
+        pub fn channels(self) -> usize {
+            self.samples().len()
+        }
+    }
+
+    /// The order of the bytes of 16-bit samples.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+    pub enum Endianness {
+        /// The most significant byte first
+        Big,
+        /// The least significant byte first
+        Little,
+    }
+
+    /// The layout of raw pixel data: the `order` of the samples of each pixel, the `depth` of
+    /// each sample in bits, which is 8 or 16, and the `endianness` of 16-bit samples. Pixels are
+    /// stored in row-major order without padding.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub struct RawLayout {
+        /// The order of the samples of each pixel
+        pub order: ChannelOrder,
+        /// The bits per sample, 8 or 16
+        pub depth: u8,
+        /// The byte order of 16-bit samples
+        pub endianness: Endianness,
+    }
+
+    impl RawLayout {
+        /// Returns the layout that keeps all samples of an image: gray or rgb samples, with
+        /// alpha if the image has an alpha channel, and 16 bits in big-endian order if the scale
+        /// exceeds 255.
+        ///
+        /// # Parameters:
+        ///  `image` - The image to store
+        pub fn of(image: &Image) -> RawLayout {
+            let order = match image.channels() {
+                1 => ChannelOrder::Gray,
+                2 => ChannelOrder::GrayAlpha,
+                3 => ChannelOrder::Rgb,
+                _ => ChannelOrder::Rgba,
+            };
+            RawLayout {
+                order,
+                depth: if image.scale > 255 { 16 } else { 8 },
+                endianness: Endianness::Big,
+            }
+        }
+
+        /// Returns the number of bytes of an image with the given size, or an error if it
+        /// exceeds the address space.
+        ///
+        /// # Parameters:
+        ///  `width` - The width of the image
+        ///  `height` - The height of the image
+        pub fn frame_size(&self, width: usize, height: usize) -> Result<usize, ImageError> {
+            let sample_bytes = usize::from(self.depth / 8);
+            Image::data_size(&[width, height, self.order.channels(), sample_bytes])
+        }
+
+        /// Returns the maximum value of a sample.
+        fn scale(&self) -> u16 {
+            if self.depth == 16 {
+                u16::MAX
+            } else {
+                255
+            }
+        }
+    }
+
+    /// Decodes raw pixel data. The image gets the magic number of the matching Netpbm format,
+    /// i.e. P5 for gray, P6 for rgb, and P7 if there is an alpha channel.
+    ///
+    /// # Parameters:
+    ///  `data` - The raw pixel data
+    ///  `width` - The width of the image
+    ///  `height` - The height of the image
+    ///  `layout` - The layout of the samples
+    ///
+    /// # Returns:
+    ///  `Result<Image, ImageError>` - The image or the reason why it could not be decoded
+    pub fn decode(
+        data: &[u8],
+        width: usize,
+        height: usize,
+        layout: &RawLayout,
+    ) -> Result<Image, ImageError> {
+        if layout.depth != 8 && layout.depth != 16 {
+            return Err(ImageError::Unsupported(
+                "Unsupported bit depth, expected 8 or 16".to_string(),
+            ));
+        }
+        if width == 0 || height == 0 {
+            return Err(ImageError::Dimensions("Invalid raw dimensions".to_string()));
+        }
+        if data.len() < layout.frame_size(width, height)? {
+            return Err(ImageError::Dimensions(
+                "Insufficient data for the specified dimensions".to_string(),
+            ));
+        }
+        let scale = layout.scale();
+        let sample_bytes = usize::from(layout.depth / 8);
+        let sample = |idx: usize| match (sample_bytes, layout.endianness) {
+            (1, _) => u16::from(data[idx]),
+            (_, Endianness::Big) => u16::from_be_bytes([data[2 * idx], data[2 * idx + 1]]),
+            (_, Endianness::Little) => u16::from_le_bytes([data[2 * idx], data[2 * idx + 1]]),
+        };
+        let samples = layout.order.samples();
+        let pixels = DMatrix::from_fn(height, width, |row, col| {
+            let idx = (row * width + col) * samples.len();
+            let mut pixel = Pixel::from_gray(scale);
+            for (offset, kind) in samples.chars().enumerate() {
+                let value = sample(idx + offset);
+                match kind {
+                    'r' => pixel.red = value,
+                    'g' => pixel.green = value,
+                    'b' => pixel.blue = value,
+                    'a' => pixel.alpha = value,
+                    _ => {
+                        pixel = Pixel {
+                            alpha: pixel.alpha,
+                            ..Pixel::from_gray(value)
+                        }
+                    }
+                }
+            }
+            pixel.normalize(scale)
+        });
+        let (magic_number, tuple_type) = match layout.order {
+            ChannelOrder::Gray => ("P5", None),
+            ChannelOrder::GrayAlpha => ("P7", Some(("GRAYSCALE_ALPHA", 2))),
+            ChannelOrder::Rgb | ChannelOrder::Bgr => ("P6", None),
+            _ => ("P7", Some(("RGB_ALPHA", 4))),
+        };
+        Ok(Image {
+            format: Format::Raw,
+            magic_number: magic_number.to_string(),
+            scale,
+            comments: Vec::new(),
+            tuple_type: tuple_type.map(|(name, depth)| TupleType {
+                name: name.to_string(),
+                depth,
+            }),
+            pixels,
+        })
+    }
+
+    /// Encodes an image as raw pixel data. Images without alpha channel are stored as opaque.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to encode
+    ///  `layout` - The layout of the samples
+    ///
+    /// # Returns:
+    ///  `Result<Vec<u8>, ImageError>` - The raw pixel data, or the reason why it could not be
+    ///  encoded
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn encode(image: &Image, layout: &RawLayout) -> Result<Vec<u8>, ImageError> {
+        if layout.depth != 8 && layout.depth != 16 {
+            return Err(ImageError::Unsupported(
+                "Unsupported bit depth, expected 8 or 16".to_string(),
+            ));
+        }
+        let (height, width) = image.pixels.shape();
+        let scale = layout.scale();
+        let has_alpha = matches!(image.channels(), 2 | 4);
+        let mut buffer = Vec::with_capacity(layout.frame_size(width, height)?);
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = image.pixels[(y, x)].denormalize(scale);
+                for kind in layout.order.samples().chars() {
+                    let sample = match kind {
+                        'r' => pixel.red,
+                        'g' => pixel.green,
+                        'b' => pixel.blue,
+                        'a' if has_alpha => pixel.alpha,
+                        'a' => scale,
+                        _ => pixel.gray(),
+                    };
+                    match (layout.depth, layout.endianness) {
+                        (16, Endianness::Big) => buffer.extend_from_slice(&sample.to_be_bytes()),
+                        (16, Endianness::Little) => {
+                            buffer.extend_from_slice(&sample.to_le_bytes());
+                        }
+                        _ => buffer.push(sample as u8),
+                    }
+                }
+            }
+        }
+        Ok(buffer)
+    }
+}
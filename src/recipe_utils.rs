@@ -0,0 +1,70 @@
+/// This crate contains the recipe files, which describe a chain of subcommands that can be applied
+/// to many images. Recipes are TOML files with a `[[step]]` table for each subcommand, whose
+/// `operation` is the name of the subcommand and whose other keys are its options, e.g.
+///
+/// ```toml
+/// [[step]]
+/// operation = "crop"
+/// x1 = 0
+/// x2 = 400
+/// y1 = 0
+/// y2 = 300
+///
+/// [[step]]
+/// operation = "seam-carve"
+/// iterations = 50
+/// direction = "v"
+/// ```
+pub mod recipe {
+    use crate::Commands;
+    use serde::Deserialize;
+    use simp::ImageError;
+    use std::fs;
+
+    /// The contents of a recipe file.
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Recipe {
+        #[serde(default)]
+        step: Vec<Commands>,
+    }
+
+    /// Reads a recipe file and checks all of its steps, before any image is touched.
+    ///
+    /// # Parameters:
+    ///  `filename` - The location of the recipe file
+    ///
+    /// # Returns:
+    ///  `Result<Vec<Commands>, ImageError>` - The steps of the recipe in order, or the reason why
+    ///  the recipe is invalid
+    pub fn load(filename: &str) -> Result<Vec<Commands>, ImageError> {
+        let contents = fs::read_to_string(filename).map_err(|err| {
+            ImageError::Io(std::io::Error::new(
+                err.kind(),
+                format!("Could not read {filename}: {err}"),
+            ))
+        })?;
+        let invalid = |message: String| ImageError::Format(format!("{filename}: {message}"));
+        let recipe: Recipe = toml::from_str(&contents).map_err(|err| invalid(err.to_string()))?;
+        if recipe.step.is_empty() {
+            return Err(invalid("The recipe has no steps".to_string()));
+        }
+        for (index, step) in recipe.step.iter().enumerate() {
+            check(step).map_err(|message| invalid(format!("Step {}: {message}", index + 1)))?;
+        }
+        Ok(recipe.step)
+    }
+
+    /// Checks the options of a step that cannot be checked while it is parsed.
+    ///
+    /// # Parameters:
+    ///  `step` - The step to check
+    fn check(step: &Commands) -> Result<(), String> {
+        match step {
+            Commands::Crop { x1, x2, y1, y2 } if x1 >= x2 || y1 >= y2 => Err(format!(
+                "The crop from ({x1}, {y1}) to ({x2}, {y2}) is empty"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
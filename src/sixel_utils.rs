@@ -0,0 +1,140 @@
+/// This crate contains the conversion of images to sixel graphics, which terminals like xterm,
+/// mlterm or foot show as inline images. A sixel is a column of six pixels, encoded as a single
+/// character. The image is drawn in bands of six rows, one color after the other.
+pub mod sixel {
+    use crate::image_utils::image::Image;
+    use color_quant::NeuQuant;
+    use std::collections::HashMap;
+    use std::fmt::Write;
+
+    /// The maximum number of colors, which most terminals support.
+    const PALETTE_SIZE: usize = 256;
+
+    /// The sampling factor of the palette quantization, between 1 (best quality) and 30
+    /// (fastest).
+    const QUANTIZATION_SPEED: i32 = 10;
+
+    /// The minimum number of repeated sixels that are run-length encoded.
+    const MIN_RUN: usize = 4;
+
+    /// Encodes an image as sixel sequence. Images with more than 256 colors are reduced to a
+    /// palette of 256 colors. Pixels that are more than half transparent are not drawn.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to encode
+    ///
+    /// # Returns:
+    ///  `String` - The sixel sequence, including the escape sequences that start and end it
+    pub fn encode(image: &Image) -> String {
+        let (height, width) = image.pixels.shape();
+        let rgba = image.binary_pixels(4, 255);
+        let (palette, indices) = quantize(&rgba);
+        let mut buffer = String::new();
+        // Pixels without a color keep the background, and the pixels have a square aspect ratio.
+        write!(buffer, "\x1bP0;1;0q\"1;1;{width};{height}").expect("Could not write header");
+        for (index, color) in palette.iter().enumerate() {
+            let [red, green, blue] = color.map(|sample| u16::from(sample) * 100 / 255);
+            write!(buffer, "#{index};2;{red};{green};{blue}").expect("Could not write palette");
+        }
+        for band in (0..height).step_by(6) {
+            // The sixels of each color in this band, as bit masks of the six rows
+            let mut sixels: Vec<Option<Vec<u8>>> = vec![None; palette.len()];
+            for y in band..(band + 6).min(height) {
+                for x in 0..width {
+                    if let Some(index) = indices[y * width + x] {
+                        let row = sixels[index].get_or_insert_with(|| vec![0; width]);
+                        row[x] |= 1 << (y - band);
+                    }
+                }
+            }
+            for (index, row) in sixels.iter().enumerate() {
+                if let Some(row) = row {
+                    write!(buffer, "#{index}").expect("Could not write color");
+                    encode_row(&mut buffer, row);
+                    // Return to the start of the band for the next color.
+                    buffer.push('$');
+                }
+            }
+            buffer.push('-');
+        }
+        buffer.push_str("\x1b\\");
+        buffer
+    }
+
+    /// Reduces the colors of an image to a palette. Images with up to 256 colors keep their
+    /// exact colors.
+    ///
+    /// # Parameters:
+    ///  `rgba` - The red, green, blue and alpha samples of the pixels, with 8 bits each
+    ///
+    /// # Returns:
+    ///  `(Vec<[u8; 3]>, Vec<Option<usize>>)` - The palette, and the index of each pixel into the
+    ///  palette, which is `None` for transparent pixels
+    fn quantize(rgba: &[u8]) -> (Vec<[u8; 3]>, Vec<Option<usize>>) {
+        let opaque = |pixel: &[u8]| pixel[3] >= 128;
+        let mut exact: HashMap<[u8; 3], usize> = HashMap::new();
+        for pixel in rgba.chunks(4).filter(|pixel| opaque(pixel)) {
+            let count = exact.len();
+            exact.entry([pixel[0], pixel[1], pixel[2]]).or_insert(count);
+            if exact.len() > PALETTE_SIZE {
+                break;
+            }
+        }
+        if exact.len() <= PALETTE_SIZE {
+            let mut palette = vec![[0; 3]; exact.len()];
+            for (&color, &index) in &exact {
+                palette[index] = color;
+            }
+            let indices = rgba
+                .chunks(4)
+                .map(|pixel| opaque(pixel).then(|| exact[&[pixel[0], pixel[1], pixel[2]]]))
+                .collect();
+            return (palette, indices);
+        }
+        let opaque_pixels: Vec<u8> = rgba
+            .chunks(4)
+            .filter(|pixel| opaque(pixel))
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect();
+        let quantizer = NeuQuant::new(QUANTIZATION_SPEED, PALETTE_SIZE, &opaque_pixels);
+        let palette = quantizer
+            .color_map_rgb()
+            .chunks(3)
+            .map(|color| [color[0], color[1], color[2]])
+            .collect();
+        let indices = rgba
+            .chunks(4)
+            .map(|pixel| {
+                opaque(pixel).then(|| quantizer.index_of(&[pixel[0], pixel[1], pixel[2], 255]))
+            })
+            .collect();
+        (palette, indices)
+    }
+
+    /// Encodes the sixels of one color in a band. Runs of equal sixels are encoded as `!`, the
+    /// length and the sixel, and trailing empty sixels are left out.
+    ///
+    /// # Parameters:
+    ///  `buffer` - The buffer to append the sixels to
+    ///  `row` - The bit masks of the sixels
+    fn encode_row(buffer: &mut String, row: &[u8]) {
+        let end = row.iter().rposition(|&bits| bits != 0).map_or(0, |x| x + 1);
+        let mut x = 0;
+        while x < end {
+            let bits = row[x];
+            let run = row[x..end]
+                .iter()
+                .take_while(|&&other| other == bits)
+                .count();
+            let sixel = char::from(63 + bits);
+            if run >= MIN_RUN {
+                write!(buffer, "!{run}{sixel}").expect("Could not write sixels");
+            } else {
+                for _ in 0..run {
+                    buffer.push(sixel);
+                }
+            }
+            x += run;
+        }
+    }
+}
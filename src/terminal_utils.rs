@@ -0,0 +1,171 @@
+/// This crate contains the preview of images in a terminal. Each character shows two pixels, one
+/// above the other: the upper half block "▀" is drawn in the color of the upper pixel, on the
+/// background color of the lower pixel. Terminals with 24-bit colors get the exact colors, all
+/// others the nearest of the 256 standard colors. Terminals that support sixel graphics can show
+/// the image itself instead.
+pub mod terminal {
+    use crate::image_utils::image::Image;
+    use crate::pixel_utils::pixel::Pixel;
+    use crate::sixel_utils::sixel;
+    use nalgebra::DMatrix;
+    use std::env;
+    use std::fmt::Write as OtherWrite;
+    use std::io::{self, Write};
+    use terminal_size::{terminal_size, terminal_size_of, Height, Width};
+
+    /// The size of the terminal if it cannot be determined, e.g. if the output is piped.
+    const DEFAULT_SIZE: (usize, usize) = (80, 24);
+
+    /// The levels of red, green and blue in the 6x6x6 color cube of the 256 colors.
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    /// Prints a preview of an image, either as sixel graphics in its full size, or with half
+    /// blocks, scaled down to fit the terminal.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to preview
+    ///  `writer` - The stream the preview is printed to
+    ///  `sixel` - Whether the preview is printed as sixel graphics
+    pub fn preview<W: Write>(image: &Image, writer: &mut W, sixel: bool) -> io::Result<()> {
+        let preview = if sixel {
+            sixel::encode(image) + "\n"
+        } else {
+            let (columns, lines) = size();
+            // One line is left for the prompt.
+            render(image, columns, lines.saturating_sub(1).max(1), truecolor())
+        };
+        writer.write_all(preview.as_bytes())?;
+        writer.flush()
+    }
+
+    /// Returns the number of columns and lines of the terminal.
+    fn size() -> (usize, usize) {
+        match terminal_size().or_else(|| terminal_size_of(io::stderr())) {
+            Some((Width(columns), Height(lines))) => (usize::from(columns), usize::from(lines)),
+            None => DEFAULT_SIZE,
+        }
+    }
+
+    /// Returns true if the terminal announces 24-bit colors.
+    fn truecolor() -> bool {
+        env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit")
+    }
+
+    /// Renders an image as text with ANSI escape codes, scaled down to fit into `columns` and
+    /// `lines`, while keeping its aspect ratio. Transparent pixels are blended with black.
+    ///
+    /// # Parameters:
+    ///  `image` - The image to render
+    ///  `columns` - The maximum number of characters per line
+    ///  `lines` - The maximum number of lines
+    ///  `truecolor` - Whether 24-bit colors are used, instead of 256 colors
+    ///
+    /// # Returns:
+    ///  `String` - The lines of the preview
+    pub fn render(image: &Image, columns: usize, lines: usize, truecolor: bool) -> String {
+        let pixels = downsample(&image.pixels, columns, 2 * lines);
+        let color = |pixel: Pixel, layer: u8| {
+            let Pixel {
+                red, green, blue, ..
+            } = pixel.denormalize(255);
+            #[allow(clippy::cast_possible_truncation)]
+            let (red, green, blue) = (red as u8, green as u8, blue as u8);
+            if truecolor {
+                format!("\x1b[{layer}8;2;{red};{green};{blue}m")
+            } else {
+                format!("\x1b[{layer}8;5;{}m", ansi256(red, green, blue))
+            }
+        };
+        let mut buffer = String::new();
+        for y in (0..pixels.nrows()).step_by(2) {
+            for x in 0..pixels.ncols() {
+                buffer.push_str(&color(pixels[(y, x)], 3));
+                if y + 1 < pixels.nrows() {
+                    buffer.push_str(&color(pixels[(y + 1, x)], 4));
+                } else {
+                    // The last line of an image with an odd height has no lower pixels.
+                    buffer.push_str("\x1b[49m");
+                }
+                buffer.push('▀');
+            }
+            writeln!(buffer, "\x1b[0m").expect("Could not write newline");
+        }
+        buffer
+    }
+
+    /// Scales a pixel matrix down to fit into `width` and `height`, while keeping its aspect
+    /// ratio. Each new pixel is the average of the pixels it covers, blended with black by their
+    /// opacity. Smaller matrices are not scaled up.
+    ///
+    /// # Parameters:
+    ///  `pixels` - The pixel matrix to scale
+    ///  `width` - The maximum width
+    ///  `height` - The maximum height
+    #[allow(clippy::cast_possible_truncation)]
+    fn downsample(pixels: &DMatrix<Pixel>, width: usize, height: usize) -> DMatrix<Pixel> {
+        let (rows, cols) = pixels.shape();
+        // Compare width / cols and height / rows without floating point numbers.
+        let (new_rows, new_cols) = if rows <= height && cols <= width {
+            (rows, cols)
+        } else if width * rows <= height * cols {
+            ((rows * width / cols).max(1), width)
+        } else {
+            (height, (cols * height / rows).max(1))
+        };
+        DMatrix::from_fn(new_rows, new_cols, |row, col| {
+            let (y1, y2) = (
+                row * rows / new_rows,
+                ((row + 1) * rows / new_rows).max(row + 1),
+            );
+            let (x1, x2) = (
+                col * cols / new_cols,
+                ((col + 1) * cols / new_cols).max(col + 1),
+            );
+            let mut sum = [0u64; 3];
+            for pixel in pixels.view((y1, x1), (y2 - y1, x2 - x1)).iter() {
+                let alpha = u64::from(pixel.alpha);
+                sum[0] += u64::from(pixel.red) * alpha / u64::from(u16::MAX);
+                sum[1] += u64::from(pixel.green) * alpha / u64::from(u16::MAX);
+                sum[2] += u64::from(pixel.blue) * alpha / u64::from(u16::MAX);
+            }
+            let count = ((y2 - y1) * (x2 - x1)) as u64;
+            Pixel::from_rgb(
+                (sum[0] / count) as u16,
+                (sum[1] / count) as u16,
+                (sum[2] / count) as u16,
+            )
+        })
+    }
+
+    /// Returns the nearest of the 256 standard colors, which is either in the 6x6x6 color cube
+    /// (16 to 231) or in the gray ramp (232 to 255).
+    ///
+    /// # Parameters:
+    ///  `red` - The red sample, between 0 and 255
+    ///  `green` - The green sample, between 0 and 255
+    ///  `blue` - The blue sample, between 0 and 255
+    #[allow(clippy::cast_possible_truncation)]
+    fn ansi256(red: u8, green: u8, blue: u8) -> u8 {
+        let distance = |r: u8, g: u8, b: u8| {
+            let diff = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2);
+            diff(r, red) + diff(g, green) + diff(b, blue)
+        };
+        let level = |sample: u8| {
+            (0..CUBE_LEVELS.len())
+                .min_by_key(|&i| (i32::from(CUBE_LEVELS[i]) - i32::from(sample)).abs())
+                .unwrap()
+        };
+        let (r, g, b) = (level(red), level(green), level(blue));
+        let cube = 16 + 36 * r + 6 * g + b;
+        let cube_distance = distance(CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+        // The gray ramp has the values 8, 18, ..., 238.
+        let gray = (u16::from(red) + u16::from(green) + u16::from(blue)) / 3;
+        let step = (gray.saturating_sub(3) / 10).min(23);
+        let value = (8 + 10 * step) as u8;
+        if distance(value, value, value) < cube_distance {
+            232 + step as u8
+        } else {
+            cube as u8
+        }
+    }
+}